@@ -0,0 +1,287 @@
+//! Reads `.gitattributes`, through which a repository overrides how PureCode
+//! (and GitHub's linguist) treat its paths.
+
+use crate::language::{Language, Languages};
+use crate::stats::SkipReason;
+use glob::{MatchOptions, Pattern};
+use std::path::{Component, Path, PathBuf};
+
+/// The state of an attribute for a path, as gitattributes(5) describes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrValue {
+    /// `attr`
+    Set,
+    /// `-attr`
+    Unset,
+    /// `attr=value`
+    Value(String),
+}
+
+impl AttrValue {
+    /// Reads `attr`, `-attr`, `attr=true` and `attr=false` as a boolean.
+    #[must_use]
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            AttrValue::Set => Some(true),
+            AttrValue::Unset => Some(false),
+            AttrValue::Value(value) => match value.as_str() {
+                "true" => Some(true),
+                "false" => Some(false),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Rule {
+    pattern: Pattern,
+    // Patterns without a slash match the file name at any depth.
+    basename_only: bool,
+    // `None` is `!attr`, which returns the attribute to unspecified.
+    attrs: Vec<(String, Option<AttrValue>)>,
+}
+
+/// The attribute rules of a repository, in the order git applies them, and
+/// the languages their names and the paths they match are detected among.
+#[derive(Clone, Default)]
+pub struct GitAttributes {
+    rules: Vec<Rule>,
+    languages: Languages,
+}
+
+impl GitAttributes {
+    /// Reads `.gitattributes` at the top of the repository, then
+    /// `.git/info/attributes`, which takes precedence. Nested
+    /// `.gitattributes` files are not read.
+    pub fn load(root: &Path) -> Self {
+        let mut attributes = Self::default();
+        for file in [
+            root.join(".gitattributes"),
+            root.join(".git/info/attributes"),
+        ] {
+            if let Ok(text) = std::fs::read_to_string(file) {
+                attributes.rules.extend(Self::parse(&text).rules);
+            }
+        }
+        attributes
+    }
+
+    /// Reads the attributes of the repository around `cwd`, if there is one.
+    pub fn discover(cwd: &Path) -> Self {
+        find_root(cwd).map_or_else(Self::default, |root| Self::load(&root))
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let rules = text
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+                let mut fields = line.split_whitespace();
+                let pattern = fields.next()?;
+                // Negated patterns are forbidden, and patterns naming a
+                // directory never match the files inside it.
+                if pattern.starts_with('!') || pattern.ends_with('/') {
+                    return None;
+                }
+                let basename_only = !pattern.contains('/');
+                Some(Rule {
+                    pattern: Pattern::new(pattern.trim_start_matches('/')).ok()?,
+                    basename_only,
+                    attrs: fields.map(parse_attr).collect(),
+                })
+            })
+            .collect();
+        Self {
+            rules,
+            languages: Languages::default(),
+        }
+    }
+
+    /// Detects languages among `languages`, the config's as well as the built-in ones.
+    #[must_use]
+    pub fn with_languages(mut self, languages: Languages) -> Self {
+        self.languages = languages;
+        self
+    }
+
+    #[must_use]
+    pub fn languages(&self) -> &Languages {
+        &self.languages
+    }
+
+    /// The value of `name` for `path`, relative to the top of the repository.
+    /// The last matching line that mentions the attribute wins.
+    pub fn get(&self, path: &str, name: &str) -> Option<&AttrValue> {
+        let options = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        let basename = path.rsplit('/').next().unwrap_or(path);
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rule| {
+                let subject = if rule.basename_only { basename } else { path };
+                rule.pattern.matches_with(subject, options)
+            })
+            .find_map(|rule| rule.attrs.iter().rev().find(|(attr, _)| attr == name))
+            .and_then(|(_, value)| value.as_ref())
+    }
+
+    /// The language of `path`: `purecode-language`, then `linguist-language`,
+    /// then its extension or file name, then the first line of its content.
+    /// Names PureCode does not know are ignored.
+    pub fn language(&self, path: &str, first_line: Option<&str>) -> Language {
+        ["purecode-language", "linguist-language"]
+            .into_iter()
+            .find_map(|name| match self.get(path, name) {
+                Some(AttrValue::Value(value)) => self.languages.from_name(value),
+                _ => None,
+            })
+            .unwrap_or_else(|| self.languages.detect(Path::new(path), first_line))
+    }
+
+    /// Why `path` is left out of the statistics, as linguist leaves
+    /// documentation and undetectable files out of a repository's languages.
+    pub fn exclusion(&self, path: &str) -> Option<SkipReason> {
+        let flag = |name| self.get(path, name).and_then(AttrValue::as_bool);
+        if flag("linguist-documentation") == Some(true) {
+            Some(SkipReason::Documentation)
+        } else if flag("linguist-detectable") == Some(false) {
+            Some(SkipReason::Undetectable)
+        } else {
+            None
+        }
+    }
+}
+
+fn parse_attr(field: &str) -> (String, Option<AttrValue>) {
+    if let Some(name) = field.strip_prefix('-') {
+        (name.to_string(), Some(AttrValue::Unset))
+    } else if let Some(name) = field.strip_prefix('!') {
+        (name.to_string(), None)
+    } else if let Some((name, value)) = field.split_once('=') {
+        (name.to_string(), Some(AttrValue::Value(value.to_string())))
+    } else {
+        (field.to_string(), Some(AttrValue::Set))
+    }
+}
+
+/// The top of the repository around `dir`: the nearest ancestor with a `.git`
+/// directory or file.
+pub fn find_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Turns a path given relative to `cwd`, or absolute, into a `/`-separated
+/// path relative to `root`. Paths outside of `root` are returned as given.
+#[must_use]
+pub fn relative_to(root: &Path, cwd: &Path, path: &Path) -> String {
+    let absolute: PathBuf = cwd
+        .join(path)
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect();
+    match absolute.strip_prefix(root) {
+        Ok(relative) => relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        Err(_) => path.to_string_lossy().into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_later_lines_override_earlier_ones() {
+        let attributes = GitAttributes::parse(
+            "# generated code\n\
+             *.pb.go linguist-generated\n\
+             vendor/** linguist-vendored\n\
+             vendor/ours/** -linguist-vendored\n\
+             /docs/*.md linguist-documentation linguist-language=Markdown\n\
+             docs/keep.md !linguist-documentation\n",
+        );
+
+        let get = |path, name| attributes.get(path, name).cloned();
+        assert_eq!(
+            get("api/v1/x.pb.go", "linguist-generated"),
+            Some(AttrValue::Set)
+        );
+        assert_eq!(
+            get("vendor/lib/a.js", "linguist-vendored"),
+            Some(AttrValue::Set)
+        );
+        assert_eq!(
+            get("vendor/ours/a.js", "linguist-vendored"),
+            Some(AttrValue::Unset)
+        );
+        assert_eq!(
+            get("docs/intro.md", "linguist-language"),
+            Some(AttrValue::Value("Markdown".to_string()))
+        );
+        // Anchored patterns match from the top of the repository only.
+        assert_eq!(get("src/docs/intro.md", "linguist-documentation"), None);
+        assert_eq!(get("docs/keep.md", "linguist-documentation"), None);
+        assert_eq!(get("main.go", "linguist-generated"), None);
+    }
+
+    #[test]
+    fn test_language_and_exclusion_overrides() {
+        let attributes = GitAttributes::parse(
+            "*.inc linguist-language=PHP\n\
+             scripts/* linguist-language=Shell purecode-language=python\n\
+             *.rs linguist-language=Klingon\n\
+             docs/** linguist-documentation\n\
+             docs/api/** -linguist-documentation\n\
+             fixtures/** -linguist-detectable\n",
+        );
+
+        assert_eq!(attributes.language("lib/header.inc", None), Language::Php);
+        // purecode-language takes precedence over linguist-language.
+        assert_eq!(
+            attributes.language("scripts/deploy", None),
+            Language::Python
+        );
+        // Unknown names fall back to the extension.
+        assert_eq!(attributes.language("src/main.rs", None), Language::Rust);
+        assert_eq!(attributes.language("README", None), Language::Other);
+
+        assert_eq!(
+            attributes.exclusion("docs/guide.md"),
+            Some(SkipReason::Documentation)
+        );
+        assert_eq!(attributes.exclusion("docs/api/gen.rs"), None);
+        assert_eq!(
+            attributes.exclusion("fixtures/sample.py"),
+            Some(SkipReason::Undetectable)
+        );
+        assert_eq!(attributes.exclusion("src/main.rs"), None);
+    }
+
+    #[test]
+    fn test_paths_relative_to_the_repository() {
+        let root = Path::new("/repo");
+        let cwd = Path::new("/repo/src");
+        assert_eq!(
+            relative_to(root, cwd, Path::new("./lib/a.rs")),
+            "src/lib/a.rs"
+        );
+        assert_eq!(relative_to(root, cwd, Path::new("/repo/b.rs")), "b.rs");
+        assert_eq!(
+            relative_to(root, cwd, Path::new("/elsewhere/c.rs")),
+            "/elsewhere/c.rs"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Attributes lines and commits to their authors, after mailmap.
+
+use crate::classifier::get_classifier;
+use crate::files;
+use crate::language::Language;
+use crate::stats::{aggregate_stats, AuthorStats, FileStats};
+use std::collections::{BTreeMap, HashSet};
+
+/// A line of a file at some revision and the author who last changed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlamedLine {
+    pub author: String,
+    pub content: String,
+}
+
+/// Accumulates per-author statistics, either from blamed snapshot lines or
+/// from the diffs of an author's commits.
+#[derive(Debug, Default)]
+pub struct AuthorTally {
+    authors: BTreeMap<String, AuthorStats>,
+    // (author, path) pairs already counted in `files`.
+    seen_files: HashSet<(String, String)>,
+}
+
+impl AuthorTally {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Classifies a file's lines in order and credits each to its blame author.
+    pub fn add_blamed_file(&mut self, path: &str, language: &Language, lines: &[BlamedLine]) {
+        let mut classifier = get_classifier(language);
+        for line in lines {
+            let line_type = classifier.classify(&line.content);
+            let author = self.entry(&line.author);
+            files::count_line(&mut author.lang_stats, line_type, &line.content);
+            if self
+                .seen_files
+                .insert((line.author.clone(), path.to_string()))
+            {
+                self.entry(&line.author).files += 1;
+            }
+        }
+    }
+
+    /// Credits one commit's changes to its author.
+    pub fn add_commit(&mut self, author: &str, files: &[FileStats]) {
+        let changes = aggregate_stats(files);
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        for path in paths {
+            if self
+                .seen_files
+                .insert((author.to_string(), path.to_string()))
+            {
+                self.entry(author).files += 1;
+            }
+        }
+
+        let entry = self.entry(author);
+        *entry.commits.get_or_insert(0) += 1;
+        let s = &mut entry.lang_stats;
+        s.total_added += changes.total_added;
+        s.total_removed += changes.total_removed;
+        s.pure_added += changes.pure_added;
+        s.pure_removed += changes.pure_removed;
+        s.pure_moved += changes.pure_moved;
+        s.reformatted_added += changes.reformatted_added;
+        s.reformatted_removed += changes.reformatted_removed;
+        s.reformatted_words_added += changes.reformatted_words_added;
+        s.reformatted_words_removed += changes.reformatted_words_removed;
+        s.comment_lines_added += changes.comment_lines_added;
+        s.comment_lines_removed += changes.comment_lines_removed;
+        s.docstring_lines_added += changes.docstring_lines_added;
+        s.docstring_lines_removed += changes.docstring_lines_removed;
+        s.blank_lines_added += changes.blank_lines_added;
+        s.blank_lines_removed += changes.blank_lines_removed;
+        s.code_words_added += changes.code_words_added;
+        s.code_words_removed += changes.code_words_removed;
+    }
+
+    /// Returns the authors with the most net pure lines first, then by name.
+    pub fn finish(self) -> Vec<AuthorStats> {
+        let mut authors: Vec<AuthorStats> = self.authors.into_values().collect();
+        authors.sort_by(|a, b| {
+            b.lang_stats
+                .net_pure()
+                .cmp(&a.lang_stats.net_pure())
+                .then_with(|| a.author.cmp(&b.author))
+        });
+        authors
+    }
+
+    fn entry(&mut self, author: &str) -> &mut AuthorStats {
+        self.authors
+            .entry(author.to_string())
+            .or_insert_with(|| AuthorStats {
+                author: author.to_string(),
+                ..AuthorStats::default()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::{FileStatus, LangStats};
+
+    fn blamed(author: &str, content: &str) -> BlamedLine {
+        BlamedLine {
+            author: author.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_blamed_lines_keep_classifier_state() {
+        let mut tally = AuthorTally::new();
+        tally.add_blamed_file(
+            "lib.rs",
+            &Language::Rust,
+            &[
+                blamed("Ada", "/*"),
+                blamed("Grace", "   explained here"),
+                blamed("Ada", "*/"),
+                blamed("Grace", "fn run() {}"),
+            ],
+        );
+        let authors = tally.finish();
+
+        assert_eq!(authors[0].author, "Grace");
+        assert_eq!(authors[0].lang_stats.pure_added, 1);
+        // Grace's line sits inside Ada's block comment.
+        assert_eq!(authors[0].lang_stats.comment_lines_added, 1);
+        assert_eq!(authors[1].author, "Ada");
+        assert_eq!(authors[1].lang_stats.comment_lines_added, 2);
+        assert_eq!(authors[1].files, 1);
+    }
+
+    #[test]
+    fn test_commits_add_up_per_author() {
+        let file = |path: &str, pure_added: i64| {
+            let mut fs = FileStats::new(path.to_string(), "Rust".to_string(), FileStatus::Added);
+            fs.lang_stats = LangStats {
+                total_added: pure_added,
+                pure_added,
+                ..LangStats::default()
+            };
+            fs
+        };
+        let mut tally = AuthorTally::new();
+        tally.add_commit("Ada", &[file("a.rs", 3)]);
+        tally.add_commit("Ada", &[file("a.rs", 2), file("b.rs", 1)]);
+        let authors = tally.finish();
+
+        assert_eq!(authors.len(), 1);
+        assert_eq!(authors[0].commits, Some(2));
+        assert_eq!(authors[0].files, 2);
+        assert_eq!(authors[0].lang_stats.pure_added, 6);
+    }
+}
@@ -0,0 +1,242 @@
+//! On-disk cache of snapshot results, so that re-running files mode only
+//! classifies files whose content changed.
+
+use crate::classifier::CLASSIFIER_VERSION;
+use crate::config::CustomLanguage;
+use crate::language::Languages;
+use crate::stats::LangStats;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const CACHE_FILENAME: &str = "snapshot.json";
+
+/// Entries the last run did not use are dropped once the cache grows past this.
+const MAX_ENTRIES: usize = 100_000;
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: String,
+    entries: HashMap<String, LangStats>,
+}
+
+#[derive(Default)]
+struct Entries {
+    stored: HashMap<String, LangStats>,
+    used: HashMap<String, LangStats>,
+    changed: bool,
+}
+
+/// Maps file content, by hash and language, to the `LangStats` it classified to.
+///
+/// Entries are only valid for the classifier version and custom languages
+/// that wrote them; a cache written with others is discarded as a whole.
+pub struct SnapshotCache {
+    dir: PathBuf,
+    version: String,
+    entries: Mutex<Entries>,
+}
+
+impl SnapshotCache {
+    /// Loads the cache in `dir`, starting empty if it is missing, unreadable or
+    /// stale. `languages` are the ones the run classifies with.
+    pub fn open(dir: &Path, languages: &Languages) -> Self {
+        let version = version(languages);
+        let stored = fs::read(dir.join(CACHE_FILENAME))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<CacheFile>(&bytes).ok())
+            .filter(|file| file.version == version)
+            .map(|file| file.entries)
+            .unwrap_or_default();
+        Self {
+            dir: dir.to_path_buf(),
+            version,
+            entries: Mutex::new(Entries {
+                stored,
+                ..Entries::default()
+            }),
+        }
+    }
+
+    pub fn get(&self, language: &str, content: &str) -> Option<LangStats> {
+        let key = key(language, content);
+        let mut entries = self.entries.lock().unwrap();
+        let lang_stats = *entries.stored.get(&key)?;
+        entries.used.insert(key, lang_stats);
+        Some(lang_stats)
+    }
+
+    pub fn insert(&self, language: &str, content: &str, lang_stats: LangStats) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.used.insert(key(language, content), lang_stats);
+        entries.changed = true;
+    }
+
+    /// Writes the cache back if this run classified anything new.
+    pub fn save(self) -> io::Result<()> {
+        let entries = self.entries.into_inner().unwrap();
+        if !entries.changed {
+            return Ok(());
+        }
+        let mut kept = entries.used;
+        if kept.len() + entries.stored.len() <= MAX_ENTRIES {
+            for (key, lang_stats) in entries.stored {
+                kept.entry(key).or_insert(lang_stats);
+            }
+        }
+
+        let file = CacheFile {
+            version: self.version,
+            entries: kept,
+        };
+        let json = serde_json::to_vec(&file).map_err(io::Error::other)?;
+        fs::create_dir_all(&self.dir)?;
+        // Concurrent runs each replace the whole file rather than interleave writes.
+        let tmp = self
+            .dir
+            .join(format!("{CACHE_FILENAME}.{}", std::process::id()));
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, self.dir.join(CACHE_FILENAME))
+    }
+}
+
+/// Where the cache lives: `.git/purecode-cache` of the repository around the
+/// working directory, otherwise `$XDG_CACHE_HOME/purecode` (`~/.cache/purecode`).
+pub fn cache_dir() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    if let Some(git_dir) = cwd
+        .ancestors()
+        .map(|dir| dir.join(".git"))
+        .find(|dir| dir.is_dir())
+    {
+        return Some(git_dir.join("purecode-cache"));
+    }
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_home.join("purecode"))
+}
+
+/// Deletes the cache in `dir`; returns whether there was one.
+pub fn clear(dir: &Path) -> io::Result<bool> {
+    match fs::remove_dir_all(dir) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// The package and classifier versions and, when the config declares
+/// languages, a hash of their definitions, which classify just as much.
+fn version(languages: &Languages) -> String {
+    let version = format!("{}+{CLASSIFIER_VERSION}", env!("CARGO_PKG_VERSION"));
+    let custom: Vec<&CustomLanguage> = languages.custom().iter().map(AsRef::as_ref).collect();
+    if custom.is_empty() {
+        return version;
+    }
+    let definitions = serde_json::to_vec(&custom).unwrap_or_default();
+    format!("{version}+{:016x}", fnv1a(&definitions))
+}
+
+fn key(language: &str, content: &str) -> String {
+    format!(
+        "{language}:{}:{:016x}",
+        content.len(),
+        fnv1a(content.as_bytes())
+    )
+}
+
+// 64-bit FNV-1a; together with the length it tells file contents apart well
+// enough for a cache, without pulling in a hashing crate.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("purecode-cache-{name}-{}", std::process::id()))
+    }
+
+    #[test]
+    fn test_entries_survive_a_save() {
+        let dir = temp_dir("save");
+        let stats = LangStats {
+            total_added: 2,
+            pure_added: 2,
+            ..LangStats::default()
+        };
+        let cache = SnapshotCache::open(&dir, &Languages::default());
+        assert!(cache.get("Rust", "fn a() {}\n").is_none());
+        cache.insert("Rust", "fn a() {}\n", stats);
+        cache.save().unwrap();
+
+        let cache = SnapshotCache::open(&dir, &Languages::default());
+        assert_eq!(cache.get("Rust", "fn a() {}\n").unwrap().pure_added, 2);
+        // The same content classifies differently in another language.
+        assert!(cache.get("Python", "fn a() {}\n").is_none());
+        assert!(cache.get("Rust", "fn b() {}\n").is_none());
+
+        assert!(clear(&dir).unwrap());
+        assert!(!clear(&dir).unwrap());
+    }
+
+    #[test]
+    fn test_stale_versions_are_discarded() {
+        let dir = temp_dir("stale");
+        fs::create_dir_all(&dir).unwrap();
+        let mut entries = HashMap::new();
+        entries.insert(key("Rust", "x"), LangStats::default());
+        let file = CacheFile {
+            version: "0.0.0+0".to_string(),
+            entries,
+        };
+        fs::write(dir.join(CACHE_FILENAME), serde_json::to_vec(&file).unwrap()).unwrap();
+
+        assert!(SnapshotCache::open(&dir, &Languages::default())
+            .get("Rust", "x")
+            .is_none());
+        clear(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_custom_languages_change_the_version() {
+        let elixir = |comment: &str| {
+            Languages::new(vec![CustomLanguage {
+                name: "Elixir".to_string(),
+                extensions: vec!["ex".to_string()],
+                line_comments: vec![comment.to_string()],
+                ..CustomLanguage::default()
+            }])
+        };
+        let builtin = version(&Languages::default());
+        assert_eq!(
+            builtin,
+            format!("{}+{CLASSIFIER_VERSION}", env!("CARGO_PKG_VERSION"))
+        );
+        assert_ne!(version(&elixir("#")), builtin);
+        assert_eq!(version(&elixir("#")), version(&elixir("#")));
+        assert_ne!(version(&elixir("#")), version(&elixir("//")));
+
+        // A cache written with other definitions is discarded.
+        let dir = temp_dir("custom");
+        let cache = SnapshotCache::open(&dir, &elixir("#"));
+        cache.insert("Elixir", "x", LangStats::default());
+        cache.save().unwrap();
+        assert!(SnapshotCache::open(&dir, &elixir("#"))
+            .get("Elixir", "x")
+            .is_some());
+        assert!(SnapshotCache::open(&dir, &elixir("//"))
+            .get("Elixir", "x")
+            .is_none());
+        clear(&dir).unwrap();
+    }
+}
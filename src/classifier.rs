@@ -1,4 +1,10 @@
+use crate::config::CustomLanguage;
 use crate::language::Language;
+use std::sync::Arc;
+
+/// Bumped whenever classification rules change, so cached snapshot results
+/// from older rules are not reused.
+pub const CLASSIFIER_VERSION: u32 = 2;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum LineType {
@@ -30,16 +36,64 @@ impl Default for PythonClassifier {
     }
 }
 
+/// A triple-quoted string still open at the end of a line.
+struct TripleString {
+    quote: &'static str,
+    docstring: bool,
+}
+
+/// A `def`/`class` header whose parameter list spans several lines.
+struct PendingHeader {
+    indent: usize,
+    depth: i32,
+}
+
+/// Classifies Python source, counting a triple-quoted string as a docstring only
+/// when it is the first statement of a module, class or function body.
 pub struct PythonClassifier {
-    in_triple_double: bool,
-    in_triple_single: bool,
+    triple: Option<TripleString>,
+    /// Minimum indentation of a statement that may still be a docstring;
+    /// `None` once the enclosing body already has a statement.
+    docstring_indent: Option<usize>,
+    header: Option<PendingHeader>,
 }
 
 impl PythonClassifier {
     pub fn new() -> Self {
         Self {
-            in_triple_double: false,
-            in_triple_single: false,
+            triple: None,
+            docstring_indent: Some(0),
+            header: None,
+        }
+    }
+
+    /// Updates docstring eligibility after a line that contains a statement.
+    fn finish_statement(
+        &mut self,
+        trimmed: &str,
+        indent: usize,
+        depth: i32,
+        last_code: Option<u8>,
+    ) {
+        let header = match self.header.take() {
+            Some(mut header) => {
+                header.depth += depth;
+                header
+            }
+            None if is_block_header(trimmed) => PendingHeader { indent, depth },
+            None => {
+                self.docstring_indent = None;
+                return;
+            }
+        };
+
+        if header.depth > 0 || self.triple.is_some() {
+            self.header = Some(header);
+        } else if last_code == Some(b':') {
+            self.docstring_indent = Some(header.indent + 1);
+        } else {
+            // One-line body such as `def f(): pass`.
+            self.docstring_indent = None;
         }
     }
 }
@@ -51,64 +105,340 @@ impl Classifier for PythonClassifier {
             return LineType::Blank;
         }
 
-        if self.in_triple_double {
-            if trimmed.contains("\"\"\"") {
-                self.in_triple_double = false;
+        let indent = line.len() - line.trim_start().len();
+        let bytes = trimmed.as_bytes();
+        let mut has_code = false;
+        let mut has_docstring = false;
+        let mut has_comment = false;
+        let mut depth = 0;
+        let mut last_code = None;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            if let Some(triple) = &self.triple {
+                if triple.docstring {
+                    has_docstring = true;
+                } else {
+                    has_code = true;
+                }
+                match close_python_string(bytes, i, triple.quote.as_bytes()) {
+                    Some(end) => {
+                        self.triple = None;
+                        last_code = Some(bytes[end - 1]);
+                        i = end;
+                    }
+                    None => break,
+                }
+                continue;
             }
-            return LineType::Docstring;
-        }
-        if self.in_triple_single {
-            if trimmed.contains("'''") {
-                self.in_triple_single = false;
+
+            let b = bytes[i];
+            if b.is_ascii_whitespace() {
+                i += 1;
+                continue;
+            }
+            if b == b'#' {
+                has_comment = true;
+                break;
             }
-            return LineType::Docstring;
+
+            if let Some((prefix_len, quote)) = open_python_string(bytes, i) {
+                if quote.len() == 3 {
+                    // f-strings and bytes are never docstrings.
+                    let docstring = i == 0
+                        && self.docstring_indent.is_some_and(|min| indent >= min)
+                        && bytes[..prefix_len]
+                            .iter()
+                            .all(|b| matches!(b.to_ascii_lowercase(), b'r' | b'u'));
+                    if docstring {
+                        has_docstring = true;
+                    } else {
+                        has_code = true;
+                    }
+                    self.triple = Some(TripleString { quote, docstring });
+                    i += prefix_len + 3;
+                } else {
+                    has_code = true;
+                    let start = i + prefix_len + 1;
+                    i = close_python_string(bytes, start, quote.as_bytes()).unwrap_or(bytes.len());
+                    last_code = Some(b);
+                }
+                continue;
+            }
+
+            has_code = true;
+            match b {
+                b'(' | b'[' | b'{' => depth += 1,
+                b')' | b']' | b'}' => depth -= 1,
+                _ => {}
+            }
+            last_code = Some(b);
+            i += 1;
         }
 
-        if trimmed.starts_with('#') {
-            return LineType::Comment;
+        if has_code || has_docstring {
+            self.finish_statement(trimmed, indent, depth, last_code);
         }
 
-        if let Some(idx) = trimmed.find("\"\"\"") {
-            if trimmed.matches("\"\"\"").count() >= 2 {
-                // Single-line docstring. Check for code before or after.
-                let before = &trimmed[..idx];
-                let after = &trimmed[idx + 3..];
-                if !before.trim().is_empty() || !after.trim().is_empty() {
-                    return LineType::Pure;
-                }
-                return LineType::Docstring;
-            } else {
-                self.in_triple_double = true;
-                return LineType::Docstring;
-            }
+        if has_code {
+            LineType::Pure
+        } else if has_docstring {
+            LineType::Docstring
+        } else if has_comment {
+            LineType::Comment
+        } else {
+            LineType::Pure
         }
+    }
+}
 
-        if let Some(idx) = trimmed.find("'''") {
-            if trimmed.matches("'''").count() >= 2 {
-                // Single-line docstring. Check for code before or after.
-                let before = &trimmed[..idx];
-                let after = &trimmed[idx + 3..];
-                if !before.trim().is_empty() || !after.trim().is_empty() {
-                    return LineType::Pure;
-                }
-                return LineType::Docstring;
-            } else {
-                self.in_triple_single = true;
-                return LineType::Docstring;
-            }
+fn is_block_header(trimmed: &str) -> bool {
+    ["def ", "async def ", "class "]
+        .iter()
+        .any(|kw| trimmed.starts_with(kw))
+}
+
+/// Matches a Python string opener at `bytes[i]`, including `r`, `b`, `f` and `u`
+/// prefixes, returning the prefix length and the quote.
+fn open_python_string(bytes: &[u8], i: usize) -> Option<(usize, &'static str)> {
+    if !at_token_start(bytes, i) {
+        return None;
+    }
+    let prefix_len = bytes[i..]
+        .iter()
+        .take_while(|b| matches!(b.to_ascii_lowercase(), b'r' | b'b' | b'f' | b'u'))
+        .count();
+    if prefix_len > 2 {
+        return None;
+    }
+    let rest = &bytes[i + prefix_len..];
+    ["\"\"\"", "'''", "\"", "'"]
+        .into_iter()
+        .find(|quote| rest.starts_with(quote.as_bytes()))
+        .map(|quote| (prefix_len, quote))
+}
+
+/// Returns the index just past `quote`, skipping backslash escapes.
+fn close_python_string(bytes: &[u8], from: usize, quote: &[u8]) -> Option<usize> {
+    let mut i = from;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            i += 2;
+        } else if bytes[i..].starts_with(quote) {
+            return Some(i + quote.len());
+        } else {
+            i += 1;
         }
+    }
+    None
+}
 
-        LineType::Pure
+/// String literal forms understood by [`CStyleClassifier`] for a given language.
+///
+/// Comment markers are only honoured outside of literals, so a URL such as
+/// `"http://x/*"` never opens a block comment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CStyleSyntax {
+    /// `"..."` strings may span lines without a trailing backslash (Rust, PHP).
+    pub multiline_strings: bool,
+    /// `'` only opens a char literal when it closes right away; otherwise it is a lifetime or label (Rust).
+    pub lifetimes: bool,
+    /// Rust raw strings: `r"..."`, `r#"..."#`, `br#"..."#`.
+    pub rust_raw_strings: bool,
+    /// C++ raw strings: `R"delim(...)delim"`.
+    pub cpp_raw_strings: bool,
+    /// Backquoted raw strings without escapes (Go).
+    pub backtick_raw_strings: bool,
+    /// Backquoted template literals with escapes (JavaScript, TypeScript).
+    pub template_literals: bool,
+    /// `"""` multi-line strings (Java, C#, Swift, Kotlin, Scala).
+    pub triple_quoted_strings: bool,
+    /// C# verbatim strings: `@"..."` with `""` as the escaped quote.
+    pub verbatim_strings: bool,
+    /// `/* /* */ */` nests, so a block comment ends only at its matching `*/`.
+    pub nested_comments: bool,
+    /// Line comment openers that mark documentation, e.g. `///` and `//!`.
+    pub doc_line_prefixes: &'static [&'static str],
+    /// Block comment openers that mark documentation, e.g. `/**` and `/*!`.
+    pub doc_block_prefixes: &'static [&'static str],
+}
+
+impl CStyleSyntax {
+    pub fn for_language(lang: &Language) -> Self {
+        let base = Self {
+            doc_block_prefixes: &["/**"],
+            ..Self::default()
+        };
+        match lang {
+            Language::Rust => Self {
+                multiline_strings: true,
+                lifetimes: true,
+                rust_raw_strings: true,
+                nested_comments: true,
+                doc_line_prefixes: &["///", "//!"],
+                doc_block_prefixes: &["/**", "/*!"],
+                ..base
+            },
+            Language::C => Self {
+                doc_line_prefixes: &["///", "//!"],
+                doc_block_prefixes: &["/**", "/*!"],
+                ..base
+            },
+            Language::Cpp => Self {
+                cpp_raw_strings: true,
+                doc_line_prefixes: &["///", "//!"],
+                doc_block_prefixes: &["/**", "/*!"],
+                ..base
+            },
+            Language::Go => Self {
+                backtick_raw_strings: true,
+                doc_block_prefixes: &[],
+                ..base
+            },
+            Language::JavaScript | Language::TypeScript => Self {
+                template_literals: true,
+                ..base
+            },
+            Language::Csharp => Self {
+                triple_quoted_strings: true,
+                verbatim_strings: true,
+                doc_line_prefixes: &["///"],
+                ..base
+            },
+            Language::Swift => Self {
+                triple_quoted_strings: true,
+                nested_comments: true,
+                doc_line_prefixes: &["///"],
+                ..base
+            },
+            Language::Kotlin | Language::Scala => Self {
+                triple_quoted_strings: true,
+                nested_comments: true,
+                ..base
+            },
+            Language::Java => Self {
+                triple_quoted_strings: true,
+                ..base
+            },
+            Language::Php => Self {
+                multiline_strings: true,
+                ..base
+            },
+            Language::Css => Self {
+                doc_block_prefixes: &[],
+                ..base
+            },
+            _ => base,
+        }
     }
 }
 
+/// A string or char literal still open at the end of a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Literal {
+    /// Literal with backslash escapes, closed by `quote`.
+    Escaped { quote: u8, multiline: bool },
+    /// Literal without escapes, closed by an exact terminator.
+    Raw(String),
+    /// C# verbatim string, where `""` is an escaped quote.
+    Verbatim,
+}
+
 pub struct CStyleClassifier {
-    in_block: bool,
+    syntax: CStyleSyntax,
+    /// Number of unclosed `/*`; never exceeds one unless the syntax allows nesting.
+    block_depth: usize,
+    /// Whether the outermost open block comment is a doc comment.
+    block_is_doc: bool,
+    literal: Option<Literal>,
 }
 
 impl CStyleClassifier {
     pub fn new() -> Self {
-        Self { in_block: false }
+        Self::with_syntax(CStyleSyntax::default())
+    }
+
+    pub fn for_language(lang: &Language) -> Self {
+        Self::with_syntax(CStyleSyntax::for_language(lang))
+    }
+
+    pub fn with_syntax(syntax: CStyleSyntax) -> Self {
+        Self {
+            syntax,
+            block_depth: 0,
+            block_is_doc: false,
+            literal: None,
+        }
+    }
+
+    fn block_type(&self) -> LineType {
+        if self.block_is_doc {
+            LineType::Docstring
+        } else {
+            LineType::Comment
+        }
+    }
+
+    /// Tries to open a literal at `bytes[i]`, returning it and the length of its opening delimiter.
+    fn open_literal(&self, bytes: &[u8], i: usize) -> Option<(Literal, usize)> {
+        let syntax = &self.syntax;
+        let rest = &bytes[i..];
+        match bytes[i] {
+            b'"' if syntax.triple_quoted_strings && rest.starts_with(b"\"\"\"") => {
+                Some((Literal::Raw("\"\"\"".to_string()), 3))
+            }
+            b'"' => Some((
+                Literal::Escaped {
+                    quote: b'"',
+                    multiline: syntax.multiline_strings,
+                },
+                1,
+            )),
+            b'\'' => {
+                if follows_number(bytes, i) {
+                    // C++14 digit separator: 1'000'000
+                    return None;
+                }
+                if syntax.lifetimes && rest.get(1) != Some(&b'\\') {
+                    let len = rest.get(1).map_or(1, |&b| utf8_len(b));
+                    if rest.get(1 + len) != Some(&b'\'') {
+                        // Lifetime or loop label: 'a, 'static
+                        return None;
+                    }
+                }
+                Some((
+                    Literal::Escaped {
+                        quote: b'\'',
+                        multiline: syntax.multiline_strings,
+                    },
+                    1,
+                ))
+            }
+            b'`' if syntax.template_literals => Some((
+                Literal::Escaped {
+                    quote: b'`',
+                    multiline: true,
+                },
+                1,
+            )),
+            b'`' if syntax.backtick_raw_strings => Some((Literal::Raw("`".to_string()), 1)),
+            b'r' | b'b' | b'c' if syntax.rust_raw_strings && at_token_start(bytes, i) => {
+                open_rust_raw(rest)
+            }
+            b'R' | b'u' | b'U' | b'L' if syntax.cpp_raw_strings && at_token_start(bytes, i) => {
+                open_cpp_raw(rest)
+            }
+            b'@' if syntax.verbatim_strings => {
+                if rest.starts_with(b"@\"") {
+                    Some((Literal::Verbatim, 2))
+                } else if rest.starts_with(b"@$\"") {
+                    Some((Literal::Verbatim, 3))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
     }
 }
 
@@ -125,44 +455,216 @@ impl Classifier for CStyleClassifier {
             return LineType::Blank;
         }
 
-        if self.in_block {
-            if trimmed.contains("*/") {
-                self.in_block = false;
-            }
+        if self.block_depth == 0 && self.literal.is_none() && trimmed.starts_with('*') {
             return LineType::Comment;
         }
 
-        if trimmed.starts_with("//") {
-            return LineType::Comment;
+        let bytes = trimmed.as_bytes();
+        let mut has_code = self.literal.is_some();
+        // Kind of the first comment on the line, if any.
+        let mut comment = (self.block_depth > 0).then(|| self.block_type());
+        let mut i = 0;
+
+        while i < bytes.len() {
+            if self.block_depth > 0 {
+                let rest = &bytes[i..];
+                if rest.starts_with(b"*/") {
+                    self.block_depth -= 1;
+                    i += 2;
+                } else if self.syntax.nested_comments && rest.starts_with(b"/*") {
+                    self.block_depth += 1;
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+                continue;
+            }
+
+            if let Some(literal) = &self.literal {
+                match close_literal(literal, bytes, i) {
+                    Some(end) => {
+                        self.literal = None;
+                        i = end;
+                    }
+                    None => break,
+                }
+                continue;
+            }
+
+            let rest = &bytes[i..];
+            if rest.starts_with(b"//") {
+                let is_doc = is_doc_opener(rest, self.syntax.doc_line_prefixes);
+                comment.get_or_insert(if is_doc {
+                    LineType::Docstring
+                } else {
+                    LineType::Comment
+                });
+                break;
+            }
+            if rest.starts_with(b"/*") {
+                self.block_depth = 1;
+                self.block_is_doc = !rest.starts_with(b"/**/")
+                    && is_doc_opener(rest, self.syntax.doc_block_prefixes);
+                comment.get_or_insert(self.block_type());
+                i += 2;
+                continue;
+            }
+            if bytes[i].is_ascii_whitespace() {
+                i += 1;
+                continue;
+            }
+
+            has_code = true;
+            match self.open_literal(bytes, i) {
+                Some((literal, len)) => {
+                    self.literal = Some(literal);
+                    i += len;
+                }
+                None => i += 1,
+            }
         }
 
-        if trimmed.starts_with('*') {
-            return LineType::Comment;
+        // Ordinary quoted literals end with the line unless it is continued with a backslash.
+        if let Some(Literal::Escaped {
+            multiline: false, ..
+        }) = self.literal
+        {
+            if !trimmed.ends_with('\\') {
+                self.literal = None;
+            }
         }
 
-        if let Some(start_idx) = trimmed.find("/*") {
-            if let Some(end_idx) = trimmed.find("*/") {
-                if end_idx > start_idx {
-                    // Single-line block comment. Check for code before or after.
-                    let before = &trimmed[..start_idx];
-                    let after = &trimmed[end_idx + 2..];
-                    if !before.trim().is_empty() || !after.trim().is_empty() {
-                        return LineType::Pure;
-                    }
-                    return LineType::Comment;
+        match comment {
+            Some(kind) if !has_code => kind,
+            _ => LineType::Pure,
+        }
+    }
+}
+
+/// Returns the index just past the literal's closing delimiter, if it closes on this line.
+fn close_literal(literal: &Literal, bytes: &[u8], from: usize) -> Option<usize> {
+    match literal {
+        Literal::Escaped { quote, .. } => {
+            let mut i = from;
+            while i < bytes.len() {
+                if bytes[i] == b'\\' {
+                    i += 2;
+                } else if bytes[i] == *quote {
+                    return Some(i + 1);
+                } else {
+                    i += 1;
                 }
             }
-            // Starts but doesn't end on same line. Check for code before /*.
-            let before = &trimmed[..start_idx];
-            if !before.trim().is_empty() {
-                self.in_block = true;
-                return LineType::Pure;
+            None
+        }
+        Literal::Raw(terminator) => {
+            find(bytes, from, terminator.as_bytes()).map(|end| end + terminator.len())
+        }
+        Literal::Verbatim => {
+            let mut i = from;
+            while i < bytes.len() {
+                if bytes[i] == b'"' {
+                    if bytes.get(i + 1) == Some(&b'"') {
+                        i += 2;
+                        continue;
+                    }
+                    return Some(i + 1);
+                }
+                i += 1;
             }
-            self.in_block = true;
-            return LineType::Comment;
+            None
         }
+    }
+}
 
-        LineType::Pure
+/// Matches `r"`, `r#"`, `br#"` or `cr"` and returns the raw literal it opens.
+fn open_rust_raw(rest: &[u8]) -> Option<(Literal, usize)> {
+    let prefix = match rest.first() {
+        Some(b'b') | Some(b'c') => 1,
+        _ => 0,
+    };
+    if rest.get(prefix) != Some(&b'r') {
+        return None;
+    }
+    let hashes = rest[prefix + 1..]
+        .iter()
+        .take_while(|&&b| b == b'#')
+        .count();
+    let quote = prefix + 1 + hashes;
+    if rest.get(quote) != Some(&b'"') {
+        return None;
+    }
+    let terminator = format!("\"{}", "#".repeat(hashes));
+    Some((Literal::Raw(terminator), quote + 1))
+}
+
+/// Matches `R"delim(`, optionally prefixed by `u8`, `u`, `U` or `L`, and returns the raw literal it opens.
+fn open_cpp_raw(rest: &[u8]) -> Option<(Literal, usize)> {
+    let prefix = if rest.starts_with(b"u8") {
+        2
+    } else if matches!(rest.first(), Some(b'u') | Some(b'U') | Some(b'L')) {
+        1
+    } else {
+        0
+    };
+    if !rest[prefix..].starts_with(b"R\"") {
+        return None;
+    }
+    let delim_start = prefix + 2;
+    let delim_len = rest[delim_start..]
+        .iter()
+        .take(17)
+        .position(|&b| b == b'(')?;
+    let delim = &rest[delim_start..delim_start + delim_len];
+    if delim
+        .iter()
+        .any(|&b| b == b')' || b == b'\\' || b.is_ascii_whitespace())
+    {
+        return None;
+    }
+    let terminator = format!("){}\"", String::from_utf8_lossy(delim));
+    Some((Literal::Raw(terminator), delim_start + delim_len + 1))
+}
+
+/// True when `rest` starts with one of `prefixes` that is not merely a longer run
+/// of the same marker, so `///` is a doc comment but `////` is not.
+fn is_doc_opener(rest: &[u8], prefixes: &[&str]) -> bool {
+    prefixes.iter().any(|prefix| {
+        let prefix = prefix.as_bytes();
+        rest.starts_with(prefix) && rest.get(prefix.len()) != prefix.last()
+    })
+}
+
+fn find(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes[from..]
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|pos| from + pos)
+}
+
+fn is_ident(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+fn at_token_start(bytes: &[u8], i: usize) -> bool {
+    i == 0 || !is_ident(bytes[i - 1])
+}
+
+/// True when the identifier-like token right before `i` is a number literal.
+fn follows_number(bytes: &[u8], i: usize) -> bool {
+    let start = bytes[..i]
+        .iter()
+        .rposition(|&b| !is_ident(b))
+        .map_or(0, |pos| pos + 1);
+    start < i && bytes[start].is_ascii_digit()
+}
+
+fn utf8_len(lead: u8) -> usize {
+    match lead {
+        0xF0..=0xFF => 4,
+        0xE0..=0xEF => 3,
+        0xC0..=0xDF => 2,
+        _ => 1,
     }
 }
 
@@ -181,6 +683,309 @@ impl Classifier for ShellClassifier {
     }
 }
 
+/// Comment and string forms of the languages whose line comments start with `--`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DashSyntax {
+    /// `/* ... */` block comments (SQL).
+    pub c_block_comments: bool,
+    /// `--[[ ... ]]` block comments and `[[ ... ]]` strings, with `=` levels as
+    /// in `--[==[ ... ]==]` (Lua).
+    pub long_brackets: bool,
+    /// Nesting `{- ... -}` block comments, `{-|` doc comments and `{-# ... #-}`
+    /// pragmas, which are code (Haskell, Elm).
+    pub brace_comments: bool,
+    /// Haddock `-- |` and `-- ^` doc comments (Haskell).
+    pub haddock: bool,
+    /// `--` followed by a symbol, as in `-->`, is an operator (Haskell).
+    pub dash_operators: bool,
+    /// `'` opens a string; otherwise it only opens a char literal such as `'"'`.
+    pub single_quoted_strings: bool,
+    /// A doubled quote, as in `'it''s'`, escapes it instead of a backslash (SQL, Ada).
+    pub doubled_quotes: bool,
+    /// Quoted strings may span lines (SQL).
+    pub multiline_strings: bool,
+    /// `"""` multi-line strings (Elm).
+    pub triple_quoted_strings: bool,
+}
+
+impl DashSyntax {
+    pub fn for_language(lang: &Language) -> Self {
+        match lang {
+            Language::Sql => Self {
+                c_block_comments: true,
+                single_quoted_strings: true,
+                doubled_quotes: true,
+                multiline_strings: true,
+                ..Self::default()
+            },
+            Language::Lua => Self {
+                long_brackets: true,
+                single_quoted_strings: true,
+                ..Self::default()
+            },
+            Language::Haskell => Self {
+                brace_comments: true,
+                haddock: true,
+                dash_operators: true,
+                ..Self::default()
+            },
+            Language::Elm => Self {
+                brace_comments: true,
+                triple_quoted_strings: true,
+                ..Self::default()
+            },
+            Language::Ada => Self {
+                doubled_quotes: true,
+                ..Self::default()
+            },
+            _ => Self::default(),
+        }
+    }
+}
+
+/// A block comment still open at the end of a line.
+struct DashBlock {
+    close: String,
+    /// The opener that nests inside the block, for `{- -}`.
+    nested_open: Option<&'static str>,
+    depth: usize,
+    is_doc: bool,
+}
+
+/// A string still open at the end of a line.
+enum DashLiteral {
+    Quoted {
+        quote: u8,
+        multiline: bool,
+    },
+    /// Closed by an exact terminator, without escapes.
+    Raw(String),
+}
+
+/// Classifies SQL, Lua, Haskell, Elm and Ada, whose line comments start with `--`.
+pub struct DashClassifier {
+    syntax: DashSyntax,
+    block: Option<DashBlock>,
+    literal: Option<DashLiteral>,
+    /// The previous line was part of a `-- |` or `-- ^` Haddock comment, which
+    /// the `--` lines right below it continue.
+    haddock: bool,
+}
+
+impl DashClassifier {
+    pub fn for_language(lang: &Language) -> Self {
+        Self {
+            syntax: DashSyntax::for_language(lang),
+            block: None,
+            literal: None,
+            haddock: false,
+        }
+    }
+
+    fn block_type(&self) -> LineType {
+        match &self.block {
+            Some(block) if block.is_doc => LineType::Docstring,
+            _ => LineType::Comment,
+        }
+    }
+
+    /// Tries to open a block comment at the start of `rest`, returning the
+    /// length of its opener.
+    fn open_block(&mut self, rest: &[u8]) -> Option<usize> {
+        let syntax = self.syntax;
+        let (block, len) = if syntax.long_brackets && rest.starts_with(b"--[") {
+            let level = long_bracket_level(&rest[2..])?;
+            let close = format!("]{}]", "=".repeat(level));
+            (block(close, None, false), level + 4)
+        } else if syntax.brace_comments && rest.starts_with(b"{-") && !rest.starts_with(b"{-#") {
+            let is_doc = rest.starts_with(b"{-|") || (syntax.haddock && rest.starts_with(b"{-^"));
+            (block("-}".to_string(), Some("{-"), is_doc), 2)
+        } else if syntax.c_block_comments && rest.starts_with(b"/*") {
+            (block("*/".to_string(), None, false), 2)
+        } else {
+            return None;
+        };
+        self.block = Some(block);
+        Some(len)
+    }
+
+    /// Tries to open a string or char literal at `bytes[i]`, returning the
+    /// length of what was consumed. Char literals are consumed whole.
+    fn open_literal(&mut self, bytes: &[u8], i: usize) -> Option<usize> {
+        let syntax = self.syntax;
+        let rest = &bytes[i..];
+        let (literal, len) = match rest[0] {
+            b'"' if syntax.triple_quoted_strings && rest.starts_with(b"\"\"\"") => {
+                (DashLiteral::Raw("\"\"\"".to_string()), 3)
+            }
+            b'"' => (
+                DashLiteral::Quoted {
+                    quote: b'"',
+                    multiline: syntax.multiline_strings,
+                },
+                1,
+            ),
+            b'\'' if syntax.single_quoted_strings => (
+                DashLiteral::Quoted {
+                    quote: b'\'',
+                    multiline: syntax.multiline_strings,
+                },
+                1,
+            ),
+            // A prime after an identifier, as in `foldl'` or `X'First`, opens nothing.
+            b'\'' if at_token_start(bytes, i) => return char_literal_len(rest),
+            b'[' if syntax.long_brackets => {
+                let level = long_bracket_level(rest)?;
+                (
+                    DashLiteral::Raw(format!("]{}]", "=".repeat(level))),
+                    level + 2,
+                )
+            }
+            _ => return None,
+        };
+        self.literal = Some(literal);
+        Some(len)
+    }
+}
+
+fn block(close: String, nested_open: Option<&'static str>, is_doc: bool) -> DashBlock {
+    DashBlock {
+        close,
+        nested_open,
+        depth: 1,
+        is_doc,
+    }
+}
+
+/// The level of a Lua long bracket `[[` or `[==[` at the start of `rest`.
+fn long_bracket_level(rest: &[u8]) -> Option<usize> {
+    let level = rest.get(1..)?.iter().take_while(|&&b| b == b'=').count();
+    (rest[0] == b'[' && rest.get(level + 1) == Some(&b'[')).then_some(level)
+}
+
+/// The length of a char literal such as `'a'` or `'\n'` at the start of `rest`.
+fn char_literal_len(rest: &[u8]) -> Option<usize> {
+    let len = if rest.get(1) == Some(&b'\\') {
+        rest.iter().skip(2).position(|&b| b == b'\'')? + 3
+    } else {
+        rest.get(1).map(|&b| utf8_len(b))? + 2
+    };
+    (rest.get(len - 1) == Some(&b'\'')).then_some(len)
+}
+
+impl Classifier for DashClassifier {
+    fn classify(&mut self, line: &str) -> LineType {
+        let trimmed = line.trim();
+        let in_haddock = std::mem::take(&mut self.haddock);
+        if trimmed.is_empty() {
+            return LineType::Blank;
+        }
+
+        let bytes = trimmed.as_bytes();
+        let mut has_code = self.literal.is_some();
+        let mut comment = self.block.is_some().then(|| self.block_type());
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let rest = &bytes[i..];
+            if let Some(block) = &mut self.block {
+                if rest.starts_with(block.close.as_bytes()) {
+                    i += block.close.len();
+                    block.depth -= 1;
+                    if block.depth == 0 {
+                        self.block = None;
+                    }
+                } else if let Some(open) = block
+                    .nested_open
+                    .filter(|open| rest.starts_with(open.as_bytes()))
+                {
+                    block.depth += 1;
+                    i += open.len();
+                } else {
+                    i += 1;
+                }
+                continue;
+            }
+
+            if let Some(literal) = &self.literal {
+                match literal {
+                    DashLiteral::Quoted { quote, .. } => {
+                        if rest[0] == *quote {
+                            if self.syntax.doubled_quotes && rest.get(1) == Some(quote) {
+                                i += 2;
+                            } else {
+                                self.literal = None;
+                                i += 1;
+                            }
+                        } else if rest[0] == b'\\' && !self.syntax.doubled_quotes {
+                            i += 2;
+                        } else {
+                            i += 1;
+                        }
+                    }
+                    DashLiteral::Raw(terminator) => match find(bytes, i, terminator.as_bytes()) {
+                        Some(end) => {
+                            i = end + terminator.len();
+                            self.literal = None;
+                        }
+                        None => break,
+                    },
+                }
+                continue;
+            }
+
+            if let Some(len) = self.open_block(rest) {
+                comment.get_or_insert(self.block_type());
+                i += len;
+                continue;
+            }
+            if rest.starts_with(b"--") {
+                let dashes = rest.iter().take_while(|&&b| b == b'-').count();
+                let next = rest.get(dashes).copied();
+                if !(self.syntax.dash_operators && next.is_some_and(is_operator_symbol)) {
+                    let text = trimmed[i + dashes..].trim_start();
+                    let opens_doc = dashes == 2 && (text.starts_with('|') || text.starts_with('^'));
+                    let is_doc = self.syntax.haddock && i == 0 && (opens_doc || in_haddock);
+                    self.haddock = is_doc;
+                    comment.get_or_insert(if is_doc {
+                        LineType::Docstring
+                    } else {
+                        LineType::Comment
+                    });
+                    break;
+                }
+                has_code = true;
+                i += dashes;
+                continue;
+            }
+            if bytes[i].is_ascii_whitespace() {
+                i += 1;
+                continue;
+            }
+
+            has_code = true;
+            i += self.open_literal(bytes, i).unwrap_or(1);
+        }
+
+        if let Some(DashLiteral::Quoted {
+            multiline: false, ..
+        }) = self.literal
+        {
+            self.literal = None;
+        }
+
+        match comment {
+            Some(kind) if !has_code => kind,
+            _ => LineType::Pure,
+        }
+    }
+}
+
+/// Characters that make `--` part of a Haskell operator such as `-->` or `--|`.
+fn is_operator_symbol(b: u8) -> bool {
+    b"!#$%&*+./<=>?@\\^|~:".contains(&b)
+}
+
 pub struct RubyClassifier {
     in_block: bool,
 }
@@ -295,7 +1100,145 @@ impl Classifier for HtmlClassifier {
     }
 }
 
-pub fn get_classifier(lang: Language) -> Box<dyn Classifier> {
+/// Classifies a language declared in the config from its comment and string
+/// delimiters alone.
+pub struct GenericClassifier {
+    syntax: Arc<CustomLanguage>,
+    /// The open block comment, by index into `block_comments`, and its depth.
+    block: Option<(usize, usize)>,
+    block_is_doc: bool,
+    /// The open multi-line string, by index into `strings`.
+    string: Option<usize>,
+}
+
+impl GenericClassifier {
+    pub fn new(syntax: Arc<CustomLanguage>) -> Self {
+        Self {
+            syntax,
+            block: None,
+            block_is_doc: false,
+            string: None,
+        }
+    }
+
+    fn block_type(&self) -> LineType {
+        if self.block_is_doc {
+            LineType::Docstring
+        } else {
+            LineType::Comment
+        }
+    }
+
+    fn comment_type(&self, rest: &[u8]) -> LineType {
+        let doc_prefixes: Vec<&str> = self
+            .syntax
+            .doc_comments
+            .iter()
+            .map(String::as_str)
+            .collect();
+        if is_doc_opener(rest, &doc_prefixes) {
+            LineType::Docstring
+        } else {
+            LineType::Comment
+        }
+    }
+}
+
+impl Classifier for GenericClassifier {
+    fn classify(&mut self, line: &str) -> LineType {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            return LineType::Blank;
+        }
+
+        let syntax = Arc::clone(&self.syntax);
+        let bytes = trimmed.as_bytes();
+        let mut has_code = self.string.is_some();
+        let mut comment = self.block.is_some().then(|| self.block_type());
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let rest = &bytes[i..];
+            if let Some((index, depth)) = self.block {
+                let (open, close) = &syntax.block_comments[index];
+                if rest.starts_with(close.as_bytes()) {
+                    self.block = (depth > 1).then_some((index, depth - 1));
+                    i += close.len();
+                } else if syntax.nested_comments && rest.starts_with(open.as_bytes()) {
+                    self.block = Some((index, depth + 1));
+                    i += open.len();
+                } else {
+                    i += 1;
+                }
+                continue;
+            }
+
+            if let Some(index) = self.string {
+                let quote = syntax.strings[index].as_bytes();
+                if rest.starts_with(quote) {
+                    self.string = None;
+                    i += quote.len();
+                } else {
+                    i += if rest[0] == b'\\' { 2 } else { 1 };
+                }
+                continue;
+            }
+
+            // Block openers first, so that Lua's `--[[` is not taken for `--`.
+            if let Some(index) =
+                longest_match(rest, syntax.block_comments.iter().map(|(open, _)| open))
+            {
+                let open = &syntax.block_comments[index].0;
+                self.block = Some((index, 1));
+                self.block_is_doc = self.comment_type(rest) == LineType::Docstring;
+                comment.get_or_insert(self.block_type());
+                i += open.len();
+                continue;
+            }
+            if longest_match(rest, syntax.line_comments.iter()).is_some() {
+                comment.get_or_insert(self.comment_type(rest));
+                break;
+            }
+            if bytes[i].is_ascii_whitespace() {
+                i += 1;
+                continue;
+            }
+
+            has_code = true;
+            match longest_match(rest, syntax.strings.iter()) {
+                Some(index) => {
+                    self.string = Some(index);
+                    i += syntax.strings[index].len();
+                }
+                None => i += 1,
+            }
+        }
+
+        // Short quotes end with the line.
+        if self
+            .string
+            .is_some_and(|index| syntax.strings[index].len() < 3)
+        {
+            self.string = None;
+        }
+
+        match comment {
+            Some(kind) if !has_code => kind,
+            _ => LineType::Pure,
+        }
+    }
+}
+
+/// The index of the longest of `tokens` that `rest` starts with.
+fn longest_match<'t>(rest: &[u8], tokens: impl Iterator<Item = &'t String>) -> Option<usize> {
+    tokens
+        .enumerate()
+        .filter(|(_, token)| !token.is_empty() && rest.starts_with(token.as_bytes()))
+        .max_by_key(|(_, token)| token.len())
+        .map(|(index, _)| index)
+}
+
+pub fn get_classifier(lang: &Language) -> Box<dyn Classifier> {
     match lang {
         Language::Python => Box::new(PythonClassifier::new()),
         Language::TypeScript
@@ -310,12 +1253,16 @@ pub fn get_classifier(lang: Language) -> Box<dyn Classifier> {
         | Language::Kotlin
         | Language::Scala
         | Language::Css
-        | Language::Rust => Box::new(CStyleClassifier::new()),
+        | Language::Rust => Box::new(CStyleClassifier::for_language(lang)),
         Language::Shell | Language::PowerShell | Language::Yaml | Language::Toml => {
             Box::new(ShellClassifier)
         }
         Language::Ruby => Box::new(RubyClassifier::new()),
         Language::Html | Language::Vue => Box::new(HtmlClassifier::new()),
+        Language::Sql | Language::Lua | Language::Haskell | Language::Elm | Language::Ada => {
+            Box::new(DashClassifier::for_language(lang))
+        }
+        Language::Custom(syntax) => Box::new(GenericClassifier::new(Arc::clone(syntax))),
         Language::Other => Box::new(DefaultClassifier),
     }
 }
@@ -332,6 +1279,113 @@ mod tests {
         assert_eq!(c.classify("   "), LineType::Blank);
     }
 
+    #[test]
+    fn test_cstyle_comment_markers_in_strings() {
+        let mut c = CStyleClassifier::for_language(&Language::Rust);
+        assert_eq!(c.classify("let url = \"http://x/*\";"), LineType::Pure);
+        assert_eq!(c.classify("let y = 2;"), LineType::Pure);
+        assert_eq!(c.classify("// real comment"), LineType::Comment);
+        assert_eq!(c.classify("let s = '\"'; // quote char"), LineType::Pure);
+        assert_eq!(c.classify("fn f<'a>(x: &'a str) {}"), LineType::Pure);
+        assert_eq!(c.classify("/* block */"), LineType::Comment);
+    }
+
+    #[test]
+    fn test_cstyle_multiline_literals() {
+        let mut rust = CStyleClassifier::for_language(&Language::Rust);
+        assert_eq!(rust.classify("let s = r#\"start"), LineType::Pure);
+        assert_eq!(
+            rust.classify("/* not a comment \" still raw"),
+            LineType::Pure
+        );
+        assert_eq!(rust.classify("\"#;"), LineType::Pure);
+        assert_eq!(rust.classify("// comment"), LineType::Comment);
+
+        let mut go = CStyleClassifier::for_language(&Language::Go);
+        assert_eq!(go.classify("q := `SELECT *"), LineType::Pure);
+        assert_eq!(go.classify("// inside raw string"), LineType::Pure);
+        assert_eq!(go.classify("`"), LineType::Pure);
+        assert_eq!(go.classify("// comment"), LineType::Comment);
+
+        let mut cpp = CStyleClassifier::for_language(&Language::Cpp);
+        assert_eq!(cpp.classify("auto s = R\"x(/* )\""), LineType::Pure);
+        assert_eq!(cpp.classify("// still raw"), LineType::Pure);
+        assert_eq!(cpp.classify(")x\";"), LineType::Pure);
+        assert_eq!(cpp.classify("int n = 1'000; // sep"), LineType::Pure);
+        assert_eq!(cpp.classify("// comment"), LineType::Comment);
+
+        let mut js = CStyleClassifier::for_language(&Language::JavaScript);
+        assert_eq!(js.classify("const t = `line /*"), LineType::Pure);
+        assert_eq!(js.classify("  // in template"), LineType::Pure);
+        assert_eq!(js.classify("`;"), LineType::Pure);
+        assert_eq!(js.classify("// comment"), LineType::Comment);
+    }
+
+    #[test]
+    fn test_cstyle_nested_block_comments() {
+        let mut rust = CStyleClassifier::for_language(&Language::Rust);
+        assert_eq!(rust.classify("/* outer"), LineType::Comment);
+        assert_eq!(rust.classify("/* inner */"), LineType::Comment);
+        assert_eq!(rust.classify("let x = 1;"), LineType::Comment);
+        assert_eq!(rust.classify("*/ let y = 2;"), LineType::Pure);
+        assert_eq!(rust.classify("let z = 3;"), LineType::Pure);
+
+        // C does not nest: the first `*/` closes the comment.
+        let mut c = CStyleClassifier::for_language(&Language::C);
+        assert_eq!(c.classify("/* outer /* inner */"), LineType::Comment);
+        assert_eq!(c.classify("int x = 1;"), LineType::Pure);
+    }
+
+    #[test]
+    fn test_cstyle_doc_comments() {
+        let mut rust = CStyleClassifier::for_language(&Language::Rust);
+        assert_eq!(rust.classify("/// Adds two numbers."), LineType::Docstring);
+        assert_eq!(rust.classify("//! Crate docs."), LineType::Docstring);
+        assert_eq!(rust.classify("//// Banner"), LineType::Comment);
+        assert_eq!(rust.classify("// plain"), LineType::Comment);
+        assert_eq!(rust.classify("/**/"), LineType::Comment);
+
+        let mut java = CStyleClassifier::for_language(&Language::Java);
+        assert_eq!(java.classify("/**"), LineType::Docstring);
+        assert_eq!(java.classify(" * @param x value"), LineType::Docstring);
+        assert_eq!(java.classify(" */"), LineType::Docstring);
+        assert_eq!(java.classify("/* plain */"), LineType::Comment);
+        assert_eq!(java.classify("/// not javadoc"), LineType::Comment);
+
+        let mut cs = CStyleClassifier::for_language(&Language::Csharp);
+        assert_eq!(
+            cs.classify("/// <summary>Docs</summary>"),
+            LineType::Docstring
+        );
+
+        let mut go = CStyleClassifier::for_language(&Language::Go);
+        assert_eq!(go.classify("/** not special */"), LineType::Comment);
+    }
+
+    #[test]
+    fn test_python_docstring_position() {
+        let mut c = PythonClassifier::new();
+        assert_eq!(c.classify(r#""""Module docs.""""#), LineType::Docstring);
+        assert_eq!(c.classify("import os"), LineType::Pure);
+        assert_eq!(c.classify(r#"sql = """"#), LineType::Pure);
+        assert_eq!(
+            c.classify("SELECT * FROM t  # not a comment"),
+            LineType::Pure
+        );
+        assert_eq!(c.classify(r#"""""#), LineType::Pure);
+        assert_eq!(c.classify("def f(a,"), LineType::Pure);
+        assert_eq!(c.classify("      b):"), LineType::Pure);
+        assert_eq!(c.classify("    r'''Raw docstring."), LineType::Docstring);
+        assert_eq!(c.classify("    '''"), LineType::Docstring);
+        assert_eq!(c.classify(r#"    run("""arg""")"#), LineType::Pure);
+        assert_eq!(c.classify(r#"    """Not first.""""#), LineType::Pure);
+        assert_eq!(c.classify("def g(): pass"), LineType::Pure);
+        assert_eq!(c.classify(r#""""After one-liner.""""#), LineType::Pure);
+        assert_eq!(c.classify("class A:"), LineType::Pure);
+        assert_eq!(c.classify("    # comment"), LineType::Comment);
+        assert_eq!(c.classify("    f'''Not a docstring.'''"), LineType::Pure);
+    }
+
     #[test]
     fn test_html_classifier() {
         let mut c = HtmlClassifier::new();
@@ -348,4 +1402,133 @@ mod tests {
         assert_eq!(c2.classify("<!--"), LineType::Comment);
         assert_eq!(c2.classify("--> <div>"), LineType::Pure);
     }
+
+    #[test]
+    fn test_generic_classifier_from_config() {
+        let config: crate::config::Config = toml::from_str(
+            r#"
+            [[languages]]
+            name = "Lua"
+            extensions = ["lua"]
+            line_comments = ["--"]
+            block_comments = [["--[[", "]]"]]
+            doc_comments = ["---"]
+            strings = ['"', "'"]
+            "#,
+        )
+        .unwrap();
+        let mut c = GenericClassifier::new(Arc::new(config.languages[0].clone()));
+        assert_eq!(c.classify("-- comment"), LineType::Comment);
+        assert_eq!(c.classify("--- Adds two numbers."), LineType::Docstring);
+        assert_eq!(c.classify("local s = \"-- not a comment\""), LineType::Pure);
+        assert_eq!(c.classify("--[["), LineType::Comment);
+        assert_eq!(c.classify("  local hidden = 1"), LineType::Comment);
+        assert_eq!(c.classify("]] return 1"), LineType::Pure);
+        assert_eq!(c.classify("return a + b -- sum"), LineType::Pure);
+    }
+
+    #[test]
+    fn test_generic_classifier_nesting_and_multiline_strings() {
+        let syntax = CustomLanguage {
+            name: "Dsl".to_string(),
+            block_comments: vec![("{-".to_string(), "-}".to_string())],
+            nested_comments: true,
+            strings: vec!["\"\"\"".to_string()],
+            ..CustomLanguage::default()
+        };
+        let mut c = GenericClassifier::new(Arc::new(syntax));
+        assert_eq!(c.classify("{- outer {- inner -}"), LineType::Comment);
+        assert_eq!(c.classify("still outer -}"), LineType::Comment);
+        assert_eq!(c.classify("text = \"\"\""), LineType::Pure);
+        assert_eq!(c.classify("{- inside a string"), LineType::Pure);
+        assert_eq!(c.classify("\"\"\""), LineType::Pure);
+        assert_eq!(c.classify("{- closed -}"), LineType::Comment);
+    }
+
+    #[test]
+    fn test_sql_classifier() {
+        let mut c = DashClassifier::for_language(&Language::Sql);
+        assert_eq!(c.classify("-- Adds the users table."), LineType::Comment);
+        assert_eq!(
+            c.classify("SELECT 'it''s -- not a comment' FROM t; -- trailing"),
+            LineType::Pure
+        );
+        assert_eq!(c.classify("/* spans"), LineType::Comment);
+        assert_eq!(c.classify("   lines */"), LineType::Comment);
+        // Strings may span lines.
+        assert_eq!(c.classify("INSERT INTO t VALUES ('first"), LineType::Pure);
+        assert_eq!(c.classify("-- still inside the string')"), LineType::Pure);
+        assert_eq!(c.classify("-- comment again"), LineType::Comment);
+    }
+
+    #[test]
+    fn test_lua_long_brackets_match_levels() {
+        let mut c = DashClassifier::for_language(&Language::Lua);
+        assert_eq!(c.classify("-- comment"), LineType::Comment);
+        assert_eq!(c.classify("--[==["), LineType::Comment);
+        assert_eq!(c.classify("  t[a[1]] = 1 ]]"), LineType::Comment);
+        assert_eq!(c.classify("]==]"), LineType::Comment);
+        assert_eq!(c.classify("local s = [["), LineType::Pure);
+        assert_eq!(c.classify("-- inside a long string"), LineType::Pure);
+        assert_eq!(c.classify("]] -- closed"), LineType::Pure);
+        assert_eq!(c.classify("print(\"--[[ not a comment\")"), LineType::Pure);
+    }
+
+    #[test]
+    fn test_haskell_and_elm_classifiers() {
+        let mut c = DashClassifier::for_language(&Language::Haskell);
+        assert_eq!(c.classify("-- | Adds two numbers."), LineType::Docstring);
+        assert_eq!(c.classify("-- ^ The first number."), LineType::Docstring);
+        assert_eq!(c.classify("add :: Int -> Int -> Int"), LineType::Pure);
+        assert_eq!(c.classify("-- plain comment"), LineType::Comment);
+        // The `--` lines right below a Haddock opener continue it, up to the
+        // first line that is not a comment.
+        assert_eq!(c.classify(""), LineType::Blank);
+        assert_eq!(
+            c.classify("-- | Subtracts two numbers,"),
+            LineType::Docstring
+        );
+        assert_eq!(c.classify("--   saturating at zero."), LineType::Docstring);
+        assert_eq!(c.classify("--"), LineType::Docstring);
+        assert_eq!(c.classify("--   >>> sub 1 2"), LineType::Docstring);
+        assert_eq!(c.classify("sub :: Int -> Int -> Int"), LineType::Pure);
+        assert_eq!(c.classify("-- plain again"), LineType::Comment);
+        assert_eq!(
+            c.classify("-- | Doc, then a blank line."),
+            LineType::Docstring
+        );
+        assert_eq!(c.classify(""), LineType::Blank);
+        assert_eq!(c.classify("-- plain after the blank"), LineType::Comment);
+        assert_eq!(c.classify("{-# LANGUAGE GADTs #-}"), LineType::Pure);
+        assert_eq!(c.classify("a --> b = a || b"), LineType::Pure);
+        assert_eq!(c.classify("{- outer {- inner -}"), LineType::Comment);
+        assert_eq!(c.classify("   still outer -}"), LineType::Comment);
+        assert_eq!(c.classify("quote = '\"' -- a char"), LineType::Pure);
+        assert_eq!(c.classify("{-| Module docs."), LineType::Docstring);
+        assert_eq!(c.classify("-}"), LineType::Docstring);
+
+        let mut c = DashClassifier::for_language(&Language::Elm);
+        assert_eq!(c.classify("{-| Elm docs -}"), LineType::Docstring);
+        assert_eq!(c.classify("text = \"\"\""), LineType::Pure);
+        assert_eq!(
+            c.classify("-- not a comment inside the string"),
+            LineType::Pure
+        );
+        assert_eq!(c.classify("\"\"\""), LineType::Pure);
+    }
+
+    #[test]
+    fn test_ada_classifier() {
+        let mut c = DashClassifier::for_language(&Language::Ada);
+        assert_eq!(c.classify("-- Greets the user."), LineType::Comment);
+        assert_eq!(
+            c.classify("Put_Line (\"Say \"\"--\"\" \"); -- out"),
+            LineType::Pure
+        );
+        assert_eq!(
+            c.classify("Dash : constant Character := '-';"),
+            LineType::Pure
+        );
+        assert_eq!(c.classify("Last : Integer := S'Last;"), LineType::Pure);
+    }
 }
@@ -13,6 +13,10 @@ pub struct Config {
     #[serde(default)]
     pub fail_on_decrease: bool,
     #[serde(default)]
+    pub exclude_reformatted: bool,
+    #[serde(default)]
+    pub exclude_generated: bool,
+    #[serde(default)]
     pub warn_only: bool,
     #[serde(default)]
     pub ci: bool,
@@ -20,6 +24,41 @@ pub struct Config {
     pub include: Vec<String>,
     #[serde(default = "default_exclude")]
     pub exclude: Vec<String>,
+    #[serde(default)]
+    pub no_ignore: bool,
+    pub jobs: Option<usize>,
+    pub max_file_size: Option<u64>,
+    #[serde(default)]
+    pub no_cache: bool,
+    #[serde(default)]
+    pub languages: Vec<CustomLanguage>,
+}
+
+/// A language declared in the config, for files the built-in languages do not
+/// cover. It takes precedence over them for the extensions and file names it lists.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CustomLanguage {
+    pub name: String,
+    /// Without the leading dot, e.g. `"ex"`; may contain dots, e.g. `"tf.json"`.
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub filenames: Vec<String>,
+    #[serde(default)]
+    pub line_comments: Vec<String>,
+    /// Opening and closing delimiters, e.g. `["/*", "*/"]`.
+    #[serde(default)]
+    pub block_comments: Vec<(String, String)>,
+    /// Whether a block comment opened inside another must be closed before it.
+    #[serde(default)]
+    pub nested_comments: bool,
+    /// Comment openers that mark documentation, e.g. `"///"` or `"/**"`.
+    #[serde(default)]
+    pub doc_comments: Vec<String>,
+    /// Quotes that delimit strings, with backslash escapes. Quotes of three or
+    /// more characters, such as `"""`, may span lines.
+    #[serde(default)]
+    pub strings: Vec<String>,
 }
 
 fn default_base() -> String {
@@ -49,10 +88,17 @@ impl Default for Config {
             max_noise_ratio: None,
             min_pure_lines: None,
             fail_on_decrease: false,
+            exclude_reformatted: false,
+            exclude_generated: false,
             warn_only: false,
             ci: false,
             include: default_include(),
             exclude: default_exclude(),
+            no_ignore: false,
+            jobs: None,
+            max_file_size: None,
+            no_cache: false,
+            languages: Vec::new(),
         }
     }
 }
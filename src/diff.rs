@@ -1,14 +1,51 @@
-use std::io::{self, BufReader};
-use std::process::{Command, Stdio};
+use crate::authors::BlamedLine;
+use crate::encoding;
+use crate::history::TreeEntry;
+use crate::parser::BlobSource;
+use crate::stats::{CommitInfo, SkipReason};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// Which uncommitted changes to diff instead of a commit range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalChanges {
+    /// The index against `HEAD`, like `git diff --cached`.
+    Staged,
+    /// The working tree against the index, like `git diff`.
+    Worktree,
+}
 
 pub fn get_git_diff(base: &str, head: &str) -> io::Result<Box<dyn std::io::BufRead>> {
-    let output = Command::new("git")
-        .args([
-            "diff",
-            &format!("{}...{}", base, head),
-            "--unified=0",
-            "--no-color",
-        ])
+    run_git_diff(&[&format!("{}...{}", base, head)])
+}
+
+pub fn get_local_diff(changes: LocalChanges) -> io::Result<Box<dyn std::io::BufRead>> {
+    match changes {
+        LocalChanges::Staged => run_git_diff(&["--cached"]),
+        LocalChanges::Worktree => run_git_diff(&[]),
+    }
+}
+
+fn run_git_diff(args: &[&str]) -> io::Result<Box<dyn std::io::BufRead>> {
+    run_git_diff_command("diff", args)
+}
+
+fn run_git_diff_command(subcommand: &str, args: &[&str]) -> io::Result<Box<dyn std::io::BufRead>> {
+    let mut command = Command::new("git");
+    command.arg(subcommand).args(args).args([
+        "--unified=0",
+        "--no-color",
+        "--find-renames",
+        "--find-copies",
+    ]);
+    let stdout = run_git(&mut command, &format!("git {subcommand}"))?;
+    let cursor = std::io::Cursor::new(stdout);
+    Ok(Box::new(BufReader::new(cursor)))
+}
+
+fn run_git(command: &mut Command, name: &str) -> io::Result<Vec<u8>> {
+    let output = command
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()?;
@@ -17,13 +54,286 @@ pub fn get_git_diff(base: &str, head: &str) -> io::Result<Box<dyn std::io::BufRe
 
     if !output.status.success() {
         let err_msg = String::from_utf8_lossy(&output.stderr);
-        return Err(io::Error::other(format!("git diff failed: {}", err_msg)));
+        return Err(io::Error::other(format!("{name} failed: {}", err_msg)));
     }
+    Ok(output.stdout)
+}
 
-    let cursor = std::io::Cursor::new(output.stdout);
-    Ok(Box::new(BufReader::new(cursor)))
+/// Lists the non-merge commits reachable from `head` but not from `base`, oldest first.
+pub fn list_commits(base: &str, head: &str) -> io::Result<Vec<CommitInfo>> {
+    run_git_log(&["--reverse", "--no-merges", &format!("{}..{}", base, head)])
+}
+
+/// Lists the first-parent history of `head`, newest first.
+pub fn list_first_parent(head: &str) -> io::Result<Vec<CommitInfo>> {
+    run_git_log(&["--first-parent", head])
+}
+
+fn run_git_log(args: &[&str]) -> io::Result<Vec<CommitInfo>> {
+    let stdout = run_git(
+        Command::new("git")
+            .arg("log")
+            .args(args)
+            .args(["--format=%H%x1f%ct%x1f%aN%x1f%s", "--"]),
+        "git log",
+    )?;
+    Ok(String::from_utf8_lossy(&stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\x1f');
+            Some(CommitInfo {
+                sha: fields.next()?.to_string(),
+                time: fields.next()?.parse().ok()?,
+                author: fields.next()?.to_string(),
+                subject: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Blames every line of a file at `rev`, with author names after mailmap.
+///
+/// `path` is relative to the top of the repository at `root`, whatever the
+/// current directory is. Returns `None` for binary files.
+pub fn blame_file(root: &Path, rev: &str, path: &str) -> io::Result<Option<Vec<BlamedLine>>> {
+    let stdout = run_git(
+        Command::new("git").arg("-C").arg(root).args([
+            "blame",
+            "--line-porcelain",
+            rev,
+            "--",
+            path,
+        ]),
+        "git blame",
+    )?;
+    let mut authors = Vec::new();
+    let mut content = Vec::new();
+    let mut author = String::new();
+    for line in stdout.split_inclusive(|&b| b == b'\n') {
+        if let Some(text) = line.strip_prefix(b"\t") {
+            authors.push(author.clone());
+            content.extend_from_slice(text);
+        } else if let Some(name) = line.strip_prefix(b"author ") {
+            author = String::from_utf8_lossy(name).trim_end().to_string();
+        }
+    }
+    let Some(text) = encoding::decode_text(&content) else {
+        return Ok(None);
+    };
+    Ok(Some(
+        text.lines()
+            .zip(authors)
+            .map(|(line, author)| BlamedLine {
+                author,
+                content: line.to_string(),
+            })
+            .collect(),
+    ))
+}
+
+/// Lists the files of a commit's tree, skipping submodules and symlinks.
+pub fn list_tree(sha: &str) -> io::Result<Vec<TreeEntry>> {
+    let stdout = run_git(
+        Command::new("git").args(["ls-tree", "-r", "-z", "-l", "--full-tree", sha]),
+        "git ls-tree",
+    )?;
+    Ok(stdout
+        .split(|&b| b == 0)
+        .filter_map(|record| {
+            // "<mode> <type> <id> <size>\t<path>", the size padded with spaces
+            let record = String::from_utf8_lossy(record);
+            let (meta, path) = record.split_once('\t')?;
+            let mut meta = meta.split_whitespace();
+            let mode = meta.next()?;
+            let kind = meta.next()?;
+            let id = meta.next()?;
+            let size = meta.next()?.parse().ok()?;
+            (kind == "blob" && mode != "120000").then(|| TreeEntry {
+                path: path.to_string(),
+                id: id.to_string(),
+                size,
+            })
+        })
+        .collect())
+}
+
+/// Diffs a commit against its first parent, or against the empty tree for a root commit.
+pub fn get_commit_diff(sha: &str) -> io::Result<Box<dyn std::io::BufRead>> {
+    run_git_diff_command("diff-tree", &["-p", "--root", "--no-commit-id", sha])
+}
+
+/// Returns the top-level directory of the working tree, which diff paths are relative to.
+pub fn repo_root() -> io::Result<PathBuf> {
+    let stdout = run_git(
+        Command::new("git").args(["rev-parse", "--show-toplevel"]),
+        "git rev-parse",
+    )?;
+    Ok(PathBuf::from(String::from_utf8_lossy(&stdout).trim_end()))
+}
+
+/// Lists untracked, non-ignored files of the whole working tree, relative to its top level.
+pub fn list_untracked() -> io::Result<Vec<String>> {
+    let stdout = run_git(
+        Command::new("git").args([
+            "ls-files",
+            "--others",
+            "--exclude-standard",
+            "--full-name",
+            "-z",
+            "--",
+            ":/",
+        ]),
+        "git ls-files",
+    )?;
+    Ok(stdout
+        .split(|&b| b == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .collect())
+}
+
+/// Reads an untracked file for classification, or says why it cannot be classified.
+pub fn read_untracked(root: &Path, path: &str) -> Result<String, SkipReason> {
+    let content = std::fs::read(root.join(path)).map_err(|_| SkipReason::Unreadable)?;
+    encoding::decode_text(&content).ok_or(SkipReason::Binary)
 }
 
 pub fn get_stdin_diff() -> Box<dyn std::io::BufRead> {
     Box::new(BufReader::new(io::stdin()))
 }
+
+/// Reads blobs from the object database through one long-lived `git cat-file --batch`.
+#[derive(Default)]
+pub struct GitBlobSource {
+    process: Option<CatFile>,
+    failed: bool,
+}
+
+struct CatFile {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl CatFile {
+    fn spawn() -> io::Result<Self> {
+        let mut child = Command::new("git")
+            .args(["cat-file", "--batch"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| io::Error::other("no stdin"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| io::Error::other("no stdout"))?;
+        Ok(Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+        })
+    }
+
+    fn read(&mut self, id: &str) -> io::Result<Option<Vec<u8>>> {
+        writeln!(self.stdin, "{id}")?;
+        self.stdin.flush()?;
+
+        // "<sha> <type> <size>" or "<id> missing"
+        let mut header = String::new();
+        self.stdout.read_line(&mut header)?;
+        let fields: Vec<&str> = header.split_whitespace().collect();
+        let [_, kind, size] = fields[..] else {
+            return Ok(None);
+        };
+        let size: usize = size.parse().map_err(io::Error::other)?;
+
+        let mut content = vec![0; size + 1]; // trailing newline
+        self.stdout.read_exact(&mut content)?;
+        content.truncate(size);
+        Ok((kind == "blob").then_some(content))
+    }
+}
+
+impl Drop for CatFile {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl BlobSource for GitBlobSource {
+    fn read_blob(&mut self, id: &str) -> Option<String> {
+        if self.failed {
+            return None;
+        }
+        if self.process.is_none() {
+            match CatFile::spawn() {
+                Ok(process) => self.process = Some(process),
+                Err(_) => {
+                    // Without git there is no point in trying again for every blob.
+                    self.failed = true;
+                    return None;
+                }
+            }
+        }
+        match self.process.as_mut()?.read(id) {
+            Ok(content) => content.and_then(|c| encoding::decode_text(&c)),
+            Err(_) => {
+                // A broken pipe or malformed reply leaves the stream unusable.
+                self.process = None;
+                self.failed = true;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    #[test]
+    fn test_blame_paths_are_relative_to_the_repository_root() {
+        let dir = std::env::temp_dir().join(format!("purecode-blame-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/a.rs"), "// note\nfn a() {}\n").unwrap();
+        fs::write(
+            dir.join("sub/b.png"),
+            b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x00\x01",
+        )
+        .unwrap();
+        git(&dir, &["init", "-q"]);
+        git(&dir, &["add", "."]);
+        git(&dir, &["commit", "-q", "-m", "base"]);
+
+        // The tests run from the crate, not from `dir`, so a path resolved
+        // against the current directory would not be found.
+        let lines = blame_file(&dir, "HEAD", "sub/a.rs").unwrap().unwrap();
+        let blamed: Vec<_> = lines
+            .iter()
+            .map(|line| (line.author.as_str(), line.content.as_str()))
+            .collect();
+        assert_eq!(blamed, [("Test", "// note"), ("Test", "fn a() {}")]);
+        assert!(blame_file(&dir, "HEAD", "sub/b.png").unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,194 @@
+//! Decodes file content that is not plain UTF-8: byte order marks, UTF-16
+//! with or without one, and Latin-1 as the fallback for anything else.
+
+use std::fmt;
+
+/// How many leading bytes are inspected to tell text from binary content.
+const SNIFF_LEN: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    /// UTF-8 behind a byte order mark, which is stripped.
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /// ISO-8859-1, assumed for content that is not valid UTF-8.
+    Latin1,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Utf8Bom => "utf-8-bom",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+            Encoding::Latin1 => "latin-1",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    pub text: String,
+    pub encoding: Encoding,
+    /// Whether invalid sequences were replaced with U+FFFD.
+    pub lossy: bool,
+}
+
+/// Decodes content as text, or returns `None` if it looks binary.
+#[must_use]
+pub fn decode(bytes: &[u8]) -> Option<Decoded> {
+    if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        return Some(decode_utf8(rest, Encoding::Utf8Bom));
+    }
+    if let Some(encoding) = utf16_encoding(bytes) {
+        let rest = match bytes {
+            [0xFF, 0xFE, rest @ ..] | [0xFE, 0xFF, rest @ ..] => rest,
+            _ => bytes,
+        };
+        return Some(decode_utf16(rest, encoding));
+    }
+    if bytes[..bytes.len().min(SNIFF_LEN)].contains(&0) {
+        return None;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some(Decoded {
+            text: text.to_string(),
+            encoding: Encoding::Utf8,
+            lossy: false,
+        }),
+        // Every byte is a Latin-1 character, so this never fails.
+        Err(_) => Some(Decoded {
+            text: bytes.iter().map(|&b| char::from(b)).collect(),
+            encoding: Encoding::Latin1,
+            lossy: false,
+        }),
+    }
+}
+
+/// Decodes content as text, or returns `None` if it looks binary.
+#[must_use]
+pub fn decode_text(bytes: &[u8]) -> Option<String> {
+    decode(bytes).map(|decoded| decoded.text)
+}
+
+/// Detects UTF-16 by its byte order mark or, without one, by NUL bytes
+/// alternating with mostly ASCII characters in the first kilobyte.
+#[must_use]
+pub fn utf16_encoding(bytes: &[u8]) -> Option<Encoding> {
+    match bytes {
+        [0xFF, 0xFE, ..] => return Some(Encoding::Utf16Le),
+        [0xFE, 0xFF, ..] => return Some(Encoding::Utf16Be),
+        _ => {}
+    }
+
+    let sample = &bytes[..bytes.len().min(SNIFF_LEN)];
+    let units = sample.len() / 2;
+    if units < 2 {
+        return None;
+    }
+    let nuls_at = |offset: usize| {
+        sample
+            .chunks_exact(2)
+            .filter(|unit| unit[offset] == 0)
+            .count()
+    };
+    let (even, odd) = (nuls_at(0), nuls_at(1));
+    if even == 0 && odd * 2 >= units {
+        Some(Encoding::Utf16Le)
+    } else if odd == 0 && even * 2 >= units {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+fn decode_utf8(bytes: &[u8], encoding: Encoding) -> Decoded {
+    let text = String::from_utf8_lossy(bytes);
+    let lossy = matches!(text, std::borrow::Cow::Owned(_));
+    Decoded {
+        text: text.into_owned(),
+        encoding,
+        lossy,
+    }
+}
+
+fn decode_utf16(bytes: &[u8], encoding: Encoding) -> Decoded {
+    let units = bytes.chunks_exact(2).map(|unit| match encoding {
+        Encoding::Utf16Be => u16::from_be_bytes([unit[0], unit[1]]),
+        _ => u16::from_le_bytes([unit[0], unit[1]]),
+    });
+    // A trailing odd byte cannot be part of any character.
+    let mut lossy = !bytes.len().is_multiple_of(2);
+    let text = char::decode_utf16(units)
+        .map(|c| {
+            c.unwrap_or_else(|_| {
+                lossy = true;
+                char::REPLACEMENT_CHARACTER
+            })
+        })
+        .collect();
+    Decoded {
+        text,
+        encoding,
+        lossy,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_utf16_with_and_without_bom() {
+        let source = "// Größe\r\nint x = 1;\r\n";
+
+        let mut with_bom = vec![0xFF, 0xFE];
+        with_bom.extend(utf16(source, false));
+        let decoded = decode(&with_bom).unwrap();
+        assert_eq!(decoded.text, source);
+        assert_eq!(decoded.encoding, Encoding::Utf16Le);
+        assert!(!decoded.lossy);
+
+        let decoded = decode(&utf16(source, true)).unwrap();
+        assert_eq!(decoded.text, source);
+        assert_eq!(decoded.encoding, Encoding::Utf16Be);
+
+        // An unpaired surrogate is replaced rather than failing the file.
+        let mut broken = vec![0xFF, 0xFE];
+        broken.extend(utf16("ab", false));
+        broken.extend([0x00, 0xD8]);
+        let decoded = decode(&broken).unwrap();
+        assert_eq!(decoded.text, "ab\u{FFFD}");
+        assert!(decoded.lossy);
+    }
+
+    #[test]
+    fn test_utf8_bom_latin1_and_binary() {
+        let decoded = decode(b"\xEF\xBB\xBF# comment\n").unwrap();
+        assert_eq!(decoded.text, "# comment\n");
+        assert_eq!(decoded.encoding, Encoding::Utf8Bom);
+
+        let decoded = decode(b"-- caf\xE9\n").unwrap();
+        assert_eq!(decoded.text, "-- café\n");
+        assert_eq!(decoded.encoding, Encoding::Latin1);
+
+        assert_eq!(decode(b"plain").unwrap().encoding, Encoding::Utf8);
+        assert!(decode(b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x00\x01").is_none());
+    }
+}
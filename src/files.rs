@@ -1,51 +1,100 @@
+use crate::cache::SnapshotCache;
 use crate::classifier::{get_classifier, LineType};
+use crate::encoding::{self, Encoding};
+use crate::generated::GeneratedDetector;
 use crate::language::Language;
-use crate::stats::{FileStats, LangStats};
+use crate::stats::{FileStats, FileStatus, LangStats, SkipReason, SkippedFile};
 use glob::Pattern;
+use ignore::{WalkBuilder, WalkState};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::BufRead;
 use std::path::Path;
-use walkdir::WalkDir;
+use std::sync::Mutex;
+
+/// Ignore files read hierarchically next to `.gitignore` and `.ignore`.
+pub const IGNORE_FILENAME: &str = ".purecodeignore";
+
+/// Files larger than this are skipped unless `max_file_size` says otherwise.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// How the directory walk of snapshot mode selects files.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WalkOptions {
+    /// Walk files that `.gitignore`, `.ignore` or `.purecodeignore` would skip.
+    pub no_ignore: bool,
+    /// Threads walking and classifying files; 0 picks one per core.
+    pub jobs: usize,
+    /// Files larger than this many bytes are skipped; 0 means no limit.
+    pub max_file_size: u64,
+}
+
+/// The files a snapshot counted and the ones it had to leave out, both sorted by path.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub files: Vec<FileStats>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+impl Snapshot {
+    fn push(&mut self, path: &Path, result: Result<FileStats, SkipReason>) {
+        match result {
+            Ok(file_stats) => self.files.push(file_stats),
+            Err(reason) => self
+                .skipped
+                .push(SkippedFile::new(path.to_string_lossy(), reason)),
+        }
+    }
+
+    /// Sorts both lists by path. Threads finish in any order, and git orders a
+    /// tree as if directory names ended in `/`, so reports stay stable.
+    #[must_use]
+    pub fn sorted(mut self) -> Self {
+        self.files.sort_by(|a, b| a.path.cmp(&b.path));
+        self.skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        self
+    }
+}
 
 pub fn analyze_files(
     paths: &[String],
     include: &[String],
     exclude: &[String],
     reader: Option<Box<dyn BufRead>>,
-) -> Result<Vec<FileStats>, std::io::Error> {
-    let mut stats = Vec::new();
+    options: WalkOptions,
+    cache: Option<&SnapshotCache>,
+    detector: &GeneratedDetector,
+) -> Result<Snapshot, std::io::Error> {
+    let mut snapshot = Snapshot::default();
 
     // Process stdin if provided (assuming list of files)
     if let Some(r) = reader {
         for line in r.lines() {
             let path_str = line?;
             let path = Path::new(&path_str);
-            if path.exists() {
-                if let Ok(fs) = process_file(path) {
-                    stats.push(fs);
-                }
-            } else {
-                eprintln!("Warning: File not found: {}", path_str);
-            }
+            snapshot.push(
+                path,
+                process_file(path, cache, detector, options.max_file_size),
+            );
         }
-        return Ok(stats);
+        return Ok(snapshot.sorted());
     }
 
-    let exclude_patterns: Vec<Pattern> = exclude
-        .iter()
-        .filter_map(|p| Pattern::new(p).ok())
-        .collect();
-
-    let include_patterns: Vec<Pattern> = include
-        .iter()
-        .filter_map(|p| Pattern::new(p).ok())
-        .collect();
+    let Some(walk) = walker(paths, options) else {
+        return Ok(snapshot);
+    };
+    let filter = PathFilter::new(include, exclude);
+    let results = Mutex::new(snapshot);
 
-    for root in paths {
-        for entry in WalkDir::new(root).into_iter().flatten() {
+    walk.run(|| {
+        let filter = &filter;
+        let results = &results;
+        Box::new(move |entry| {
+            let Ok(entry) = entry else {
+                return WalkState::Continue;
+            };
             let path = entry.path();
             if path.is_dir() {
-                continue;
+                return WalkState::Continue;
             }
 
             let path_str = path.to_string_lossy();
@@ -55,80 +104,179 @@ pub fn analyze_files(
                 &path_str
             };
 
-            if exclude_patterns.iter().any(|p| p.matches(clean_path)) {
-                continue;
-            }
+            let result = if filter.matches(clean_path) {
+                process_file(path, cache, detector, options.max_file_size)
+            } else {
+                Err(SkipReason::Excluded)
+            };
+            results.lock().unwrap().push(path, result);
+            WalkState::Continue
+        })
+    });
 
-            if !include_patterns.iter().any(|p| p.matches(clean_path)) {
-                continue;
-            }
+    Ok(results.into_inner().unwrap().sorted())
+}
 
-            if let Ok(fs) = process_file(path) {
-                stats.push(fs);
-            }
+/// Walks `roots` in parallel, honoring ignore files the way ripgrep does:
+/// `.gitignore`, `.git/info/exclude` and the global gitignore inside git
+/// repositories, and `.ignore` and `.purecodeignore` anywhere, each applying
+/// to its directory and below. Unlike ripgrep, hidden files are not skipped.
+fn walker(roots: &[String], options: WalkOptions) -> Option<ignore::WalkParallel> {
+    let (first, rest) = roots.split_first()?;
+    let mut builder = WalkBuilder::new(first);
+    for root in rest {
+        builder.add(root);
+    }
+    builder
+        .standard_filters(!options.no_ignore)
+        .hidden(false)
+        .threads(options.jobs)
+        // Hidden files are walked, but never the repository itself.
+        .filter_entry(|entry| entry.file_name() != ".git");
+    if !options.no_ignore {
+        builder.add_custom_ignore_filename(IGNORE_FILENAME);
+    }
+    Some(builder.build_parallel())
+}
+
+/// The `include` and `exclude` globs of the config, compiled once.
+pub struct PathFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Self {
+        let compile = |patterns: &[String]| -> Vec<Pattern> {
+            patterns
+                .iter()
+                .filter_map(|p| Pattern::new(p).ok())
+                .collect()
+        };
+        Self {
+            include: compile(include),
+            exclude: compile(exclude),
         }
     }
 
-    Ok(stats)
+    /// Whether a path, relative to the analyzed root, is included and not excluded.
+    #[must_use]
+    pub fn matches(&self, path: &str) -> bool {
+        !self.exclude.iter().any(|p| p.matches(path))
+            && self.include.iter().any(|p| p.matches(path))
+    }
 }
 
-fn process_file(path: &Path) -> Result<FileStats, std::io::Error> {
-    let language = Language::from_path(path);
+/// Whether `path` is one of `roots` or inside one of them; `.` stands for everything.
+#[must_use]
+pub fn is_under(path: &str, roots: &[String]) -> bool {
+    roots.iter().any(|root| {
+        let root = root.trim_start_matches("./").trim_end_matches('/');
+        root.is_empty()
+            || root == "."
+            || path == root
+            || path
+                .strip_prefix(root)
+                .is_some_and(|rest| rest.starts_with('/'))
+    })
+}
 
+fn process_file(
+    path: &Path,
+    cache: Option<&SnapshotCache>,
+    detector: &GeneratedDetector,
+    max_file_size: u64,
+) -> Result<FileStats, SkipReason> {
+    let repo_path = detector.repo_path(path);
+    if let Some(reason) = detector.attributes().exclusion(&repo_path) {
+        return Err(reason);
+    }
+    let metadata = std::fs::metadata(path).map_err(|_| SkipReason::Unreadable)?;
+    if max_file_size > 0 && metadata.len() > max_file_size {
+        return Err(SkipReason::TooLarge);
+    }
     // Use a separate check
-    if is_binary(path)? {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "Binary file",
-        ));
+    if is_binary(path).map_err(|_| SkipReason::Unreadable)? {
+        return Err(SkipReason::Binary);
     }
 
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
+    let bytes = std::fs::read(path).map_err(|_| SkipReason::Unreadable)?;
+    let decoded = encoding::decode(&bytes).ok_or(SkipReason::Binary)?;
+    if decoded.lossy {
+        eprintln!(
+            "Warning: Replaced invalid {} sequences in {}",
+            decoded.encoding,
+            path.display()
+        );
+    }
+    let content = decoded.text;
+    let path_str = path.to_string_lossy();
 
-    let mut classifier = get_classifier(language);
-    let mut lang_stats = LangStats::default();
+    let language = detector
+        .attributes()
+        .language(&repo_path, content.lines().next());
 
-    for line_result in reader.lines() {
-        match line_result {
-            Ok(line) => {
-                lang_stats.total_added += 1; // Snapshot mode: everything is added
-                match classifier.classify(&line) {
-                    LineType::Pure => {
-                        lang_stats.pure_added += 1;
-                        lang_stats.code_words_added += line.split_whitespace().count() as i64;
-                    }
-                    LineType::Comment => lang_stats.comment_lines_added += 1,
-                    LineType::Docstring => lang_stats.docstring_lines_added += 1,
-                    LineType::Blank => lang_stats.blank_lines_added += 1,
-                }
-            }
-            Err(_) => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "Read error",
-                ))
+    let mut file_stats = match cache {
+        None => analyze_content(&path_str, &language, &content),
+        Some(cache) => {
+            let name = language.to_string();
+            if let Some(lang_stats) = cache.get(&name, &content) {
+                let mut file_stats = FileStats::new(path_str.into_owned(), name, FileStatus::Added);
+                file_stats.lang_stats = lang_stats;
+                file_stats
+            } else {
+                let file_stats = analyze_content(&path_str, &language, &content);
+                cache.insert(&name, &content, file_stats.lang_stats);
+                file_stats
             }
         }
+    };
+    if decoded.encoding != Encoding::Utf8 {
+        file_stats.encoding = Some(decoded.encoding.to_string());
+    }
+    file_stats.generated = detector.is_generated(&repo_path, &language, content.lines());
+    Ok(file_stats)
+}
+
+/// Classifies every line of a file's content; in snapshot mode everything is added.
+pub fn analyze_content(path: &str, language: &Language, content: &str) -> FileStats {
+    let mut classifier = get_classifier(language);
+    let mut lang_stats = LangStats::default();
+
+    for line in content.lines() {
+        count_line(&mut lang_stats, classifier.classify(line), line);
     }
 
-    Ok(FileStats {
-        path: path.to_string_lossy().to_string(),
-        language: language.to_string(),
-        lang_stats,
-    })
+    let mut file_stats = FileStats::new(path.to_string(), language.to_string(), FileStatus::Added);
+    file_stats.lang_stats = lang_stats;
+    file_stats
 }
 
-fn is_binary(path: &Path) -> Result<bool, std::io::Error> {
+/// Counts a classified line as added.
+pub fn count_line(lang_stats: &mut LangStats, line_type: LineType, line: &str) {
+    lang_stats.total_added += 1;
+    match line_type {
+        LineType::Pure => {
+            lang_stats.pure_added += 1;
+            lang_stats.code_words_added += line.split_whitespace().count() as i64;
+        }
+        LineType::Comment => lang_stats.comment_lines_added += 1,
+        LineType::Docstring => lang_stats.docstring_lines_added += 1,
+        LineType::Blank => lang_stats.blank_lines_added += 1,
+    }
+}
+
+pub fn is_binary(path: &Path) -> Result<bool, std::io::Error> {
     let mut file = File::open(path)?;
     let mut buffer = [0; 1024];
     use std::io::Read;
     let n = file.read(&mut buffer)?;
-    if n == 0 {
-        return Ok(false);
-    } // Empty file is not binary
-    if buffer[..n].contains(&0) {
-        return Ok(true);
-    }
-    Ok(false)
+    Ok(looks_binary(&buffer[..n]))
+}
+
+/// Whether content has a NUL byte in its first kilobyte and is not UTF-16.
+/// Empty content is not binary.
+#[must_use]
+pub fn looks_binary(content: &[u8]) -> bool {
+    content[..content.len().min(1024)].contains(&0) && encoding::utf16_encoding(content).is_none()
 }
@@ -0,0 +1,265 @@
+//! Flags generated and vendored files, whose lines would otherwise distort
+//! both snapshot and diff statistics.
+
+use crate::attributes::{self, GitAttributes};
+use crate::classifier::{get_classifier, LineType};
+use crate::language::{Language, Languages};
+use std::path::{Path, PathBuf};
+
+/// How generator comments start: the `@generated` tag, Go's convention and
+/// the wording of protoc, Thrift and many others. Any comment syntax will do.
+const MARKER_PREFIXES: &[&str] = &["@generated", "Code generated by", "Generated by"];
+
+/// The warning generators put anywhere in their header comment.
+const MARKER_WARNING: &str = "DO NOT EDIT";
+
+/// How many leading lines are searched for a marker.
+const HEADER_LINES: usize = 10;
+
+/// Average line length above which a script or stylesheet counts as minified,
+/// the threshold linguist uses.
+const MINIFIED_LINE_LENGTH: usize = 110;
+
+const MINIFIABLE_EXTENSIONS: &[&str] = &["js", "mjs", "cjs", "css", "json"];
+
+/// Directories holding third-party code.
+const VENDOR_DIRS: &[&str] = &[
+    "vendor",
+    "third_party",
+    "third-party",
+    "node_modules",
+    "bower_components",
+];
+
+/// Lockfiles and similar files that tools write.
+const GENERATED_NAMES: &[&str] = &[
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "Cargo.lock",
+    "composer.lock",
+    "Gemfile.lock",
+    "poetry.lock",
+    "Pipfile.lock",
+    "go.sum",
+];
+
+const GENERATED_SUFFIXES: &[&str] = &[
+    ".min.js",
+    ".min.css",
+    "_pb2.py",
+    "_pb2_grpc.py",
+    ".pb.go",
+    ".pb.cc",
+    ".pb.h",
+];
+
+/// Decides whether a file is generated or vendored.
+///
+/// `linguist-generated` and `linguist-vendored` in `.gitattributes` take
+/// precedence, in both directions, over markers and path and content heuristics.
+#[derive(Clone, Default)]
+pub struct GeneratedDetector {
+    attributes: GitAttributes,
+    root: PathBuf,
+    cwd: PathBuf,
+}
+
+impl GeneratedDetector {
+    /// Reads the attributes of the repository around `cwd`, if there is one.
+    pub fn discover(cwd: &Path) -> Self {
+        let root = attributes::find_root(cwd).unwrap_or_else(|| cwd.to_path_buf());
+        Self {
+            attributes: GitAttributes::load(&root),
+            root,
+            cwd: cwd.to_path_buf(),
+        }
+    }
+
+    /// Detects languages among `languages`, the config's as well as the built-in ones.
+    #[must_use]
+    pub fn with_languages(mut self, languages: Languages) -> Self {
+        self.attributes = self.attributes.with_languages(languages);
+        self
+    }
+
+    /// The attributes the detector consults, which also override languages.
+    #[must_use]
+    pub fn attributes(&self) -> &GitAttributes {
+        &self.attributes
+    }
+
+    /// Turns a path relative to the working directory into one relative to the
+    /// top of the repository, as `.gitattributes` patterns expect.
+    #[must_use]
+    pub fn repo_path(&self, path: &Path) -> String {
+        attributes::relative_to(&self.root, &self.cwd, path)
+    }
+
+    /// Judges `path`, relative to the top of the repository, by its attributes,
+    /// its name and the given lines, which start at the top of the file and
+    /// are read as `language` so that only comments can carry a marker.
+    pub fn is_generated<'a>(
+        &self,
+        path: &str,
+        language: &Language,
+        lines: impl IntoIterator<Item = &'a str>,
+    ) -> bool {
+        let attribute = |name| {
+            self.attributes
+                .get(path, name)
+                .and_then(|value| value.as_bool())
+        };
+        let vendored = attribute("linguist-vendored").unwrap_or_else(|| is_vendored_path(path));
+        vendored
+            || attribute("linguist-generated").unwrap_or_else(|| {
+                is_generated_path(path) || looks_generated(path, language, lines)
+            })
+    }
+}
+
+fn is_vendored_path(path: &str) -> bool {
+    let mut dirs = path.split('/');
+    dirs.next_back();
+    dirs.any(|dir| VENDOR_DIRS.contains(&dir))
+}
+
+fn is_generated_path(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    GENERATED_NAMES.contains(&name) || GENERATED_SUFFIXES.iter().any(|s| name.ends_with(s))
+}
+
+/// Looks for a generator marker in the comments of the first lines and, for
+/// scripts and stylesheets, for minified content.
+fn looks_generated<'a>(
+    path: &str,
+    language: &Language,
+    lines: impl IntoIterator<Item = &'a str>,
+) -> bool {
+    let minifiable = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| MINIFIABLE_EXTENSIONS.contains(&e));
+    let mut classifier = get_classifier(language);
+    let mut count = 0;
+    let mut length = 0;
+    for line in lines {
+        if count < HEADER_LINES && classifier.classify(line) == LineType::Comment && is_marker(line)
+        {
+            return true;
+        }
+        if !minifiable && count >= HEADER_LINES {
+            break;
+        }
+        count += 1;
+        length += line.len();
+    }
+    minifiable && count > 0 && length / count > MINIFIED_LINE_LENGTH
+}
+
+/// Whether a comment line is a generator marker, whatever its comment syntax.
+fn is_marker(line: &str) -> bool {
+    let text = line
+        .trim()
+        .trim_start_matches(['/', '*', '#', '-', ';', '!', '<', '%'])
+        .trim_end_matches("*/")
+        .trim_end_matches("-->")
+        .trim();
+    MARKER_PREFIXES.iter().any(|p| text.starts_with(p)) || text.contains(MARKER_WARNING)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markers_paths_and_minified_content() {
+        let detector = GeneratedDetector::default();
+        let header = [
+            "// Code generated by protoc-gen-go. DO NOT EDIT.",
+            "package api",
+        ];
+        assert!(detector.is_generated("api/api.go", &Language::Go, header));
+        assert!(detector.is_generated("gen.py", &Language::Python, ["# @generated by tool"]));
+        assert!(detector.is_generated("Cargo.lock", &Language::Other, []));
+        assert!(detector.is_generated("web/vendor/jquery/jquery.js", &Language::JavaScript, []));
+        assert!(!detector.is_generated("src/vendor.rs", &Language::Rust, []));
+
+        let minified = "a".repeat(500);
+        assert!(detector.is_generated("dist/app.js", &Language::JavaScript, [minified.as_str()]));
+        // Long lines in other languages are not taken as minification.
+        assert!(!detector.is_generated("src/data.py", &Language::Python, [minified.as_str()]));
+
+        // A marker far below the header does not count.
+        let mut lines = vec!["fn main() {}"; HEADER_LINES];
+        lines.push("// @generated");
+        assert!(!detector.is_generated("src/main.rs", &Language::Rust, lines));
+    }
+
+    #[test]
+    fn test_markers_must_be_anchored_comments() {
+        let detector = GeneratedDetector::default();
+        let is_generated = |line| detector.is_generated("src/lib.rs", &Language::Rust, [line]);
+        assert!(is_generated("/* @generated */"));
+        assert!(is_generated("// Code generated by build.rs. DO NOT EDIT."));
+        assert!(is_generated("// DO NOT EDIT"));
+        // Inside a string literal, or not where the conventions put them.
+        assert!(!is_generated(r#"const TAG: &str = "// @generated";"#));
+        assert!(!is_generated(
+            r#"let s = "Code generated by x. DO NOT EDIT.";"#
+        ));
+        assert!(!is_generated("// See the @generated tag in the header."));
+        assert!(!is_generated("// Regenerated by hand from the spec."));
+    }
+
+    #[test]
+    fn test_protoc_headers_are_markers() {
+        let detector = GeneratedDetector::default();
+        let java = [
+            "// Generated by the protocol buffer compiler.  DO NOT EDIT!",
+            "// source: api.proto",
+            "package api;",
+        ];
+        assert!(detector.is_generated("src/Api.java", &Language::Java, java));
+        let csharp = [
+            "// <auto-generated>",
+            "//     Generated by the protocol buffer compiler.  DO NOT EDIT!",
+            "//     source: api.proto",
+            "// </auto-generated>",
+        ];
+        assert!(detector.is_generated("Api.cs", &Language::Csharp, csharp));
+        let typescript = [
+            "/* eslint-disable */",
+            "// Code generated by protoc-gen-ts_proto. DO NOT EDIT.",
+        ];
+        assert!(detector.is_generated("src/api.ts", &Language::TypeScript, typescript));
+        let python = [
+            "# -*- coding: utf-8 -*-",
+            "# Generated by the protocol buffer compiler.  DO NOT EDIT!",
+        ];
+        assert!(detector.is_generated("api/service.py", &Language::Python, python));
+    }
+
+    #[test]
+    fn test_detector_source_is_not_generated() {
+        let detector = GeneratedDetector::default();
+        let source = include_str!("generated.rs");
+        assert!(!detector.is_generated("src/generated.rs", &Language::Rust, source.lines()));
+    }
+
+    #[test]
+    fn test_attributes_override_heuristics() {
+        let detector = GeneratedDetector {
+            attributes: GitAttributes::parse(
+                "vendor/** -linguist-vendored\n\
+                 *.lock -linguist-generated\n\
+                 schema/*.rs linguist-generated=true\n",
+            ),
+            ..GeneratedDetector::default()
+        };
+        assert!(!detector.is_generated("vendor/patched/lib.c", &Language::C, []));
+        assert!(!detector.is_generated("Cargo.lock", &Language::Other, []));
+        assert!(detector.is_generated("schema/tables.rs", &Language::Rust, ["pub struct Table;"]));
+    }
+}
@@ -0,0 +1,398 @@
+//! Samples first-parent history and measures the snapshot of each sampled tree,
+//! for a time series of pure lines, comment density and docstring density.
+
+use crate::files::{self, PathFilter};
+use crate::generated::GeneratedDetector;
+use crate::parser::BlobSource;
+use crate::stats::{CommitInfo, FileStats, HistoryRow, LangStats, SkipReason, SkippedFile};
+use std::collections::{BTreeMap, HashMap};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// How far apart samples are. Dates are UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Commit,
+    Day,
+    /// Weeks start on Monday.
+    Week,
+}
+
+/// A file of a tree, named by its blob id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub path: String,
+    pub id: String,
+    /// The size of the blob in bytes.
+    pub size: u64,
+}
+
+/// A sampled commit and the date it stands for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    pub date: String,
+    pub commit: CommitInfo,
+}
+
+/// Picks the commits to measure from first-parent history, newest first as
+/// git lists it, and returns them oldest first.
+///
+/// With a day or week step, the newest commit of each period stands for the
+/// state of the code at the end of that period.
+pub fn sample_commits(commits: Vec<CommitInfo>, since: Option<i64>, step: Step) -> Vec<Sample> {
+    let mut samples: Vec<Sample> = Vec::new();
+    let mut last_period = None;
+    for commit in commits {
+        // Rebased commits and clock skew can put older commits between newer
+        // ones, so each commit is checked rather than stopping at the first.
+        if since.is_some_and(|since| commit.time < since) {
+            continue;
+        }
+        let day = commit.time.div_euclid(SECONDS_PER_DAY);
+        let period = match step {
+            Step::Commit => None,
+            Step::Day => Some(day),
+            Step::Week => Some(day - (day + 3).rem_euclid(7)),
+        };
+        if period.is_some() && period == last_period {
+            continue;
+        }
+        last_period = period;
+        samples.push(Sample {
+            date: format_date(period.unwrap_or(day)),
+            commit,
+        });
+    }
+    samples.reverse();
+    samples
+}
+
+/// Runs the snapshot pipeline over trees read from the object database.
+///
+/// Blobs are classified once per path and remembered by id, since most files
+/// do not change between two samples.
+pub struct Snapshotter {
+    filter: PathFilter,
+    detector: GeneratedDetector,
+    max_file_size: u64,
+    cache: HashMap<(String, String), Option<FileStats>>,
+}
+
+impl Snapshotter {
+    pub fn new(filter: PathFilter, detector: GeneratedDetector) -> Self {
+        Self {
+            filter,
+            detector,
+            max_file_size: 0,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Skips blobs larger than this many bytes; 0, the default, means no limit.
+    #[must_use]
+    pub fn with_max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// Measures the files of `tree`, listing the ones it leaves out with the
+    /// same reasons as a walk of the working tree.
+    pub fn snapshot(&mut self, tree: &[TreeEntry], blobs: &mut dyn BlobSource) -> files::Snapshot {
+        let mut snapshot = files::Snapshot::default();
+        for entry in tree {
+            let attributes = self.detector.attributes();
+            let skip = if !self.filter.matches(&entry.path) {
+                Some(SkipReason::Excluded)
+            } else if let Some(reason) = attributes.exclusion(&entry.path) {
+                Some(reason)
+            } else if self.max_file_size > 0 && entry.size > self.max_file_size {
+                Some(SkipReason::TooLarge)
+            } else {
+                None
+            };
+            if let Some(reason) = skip {
+                snapshot
+                    .skipped
+                    .push(SkippedFile::new(entry.path.clone(), reason));
+                continue;
+            }
+            // The language and whether the file is generated depend on the path
+            // as well as the content.
+            let key = (entry.id.clone(), entry.path.clone());
+            let cached = self.cache.entry(key).or_insert_with(|| {
+                let content = blobs.read_blob(&entry.id)?;
+                let language = attributes.language(&entry.path, content.lines().next());
+                let mut file_stats = files::analyze_content(&entry.path, &language, &content);
+                file_stats.generated =
+                    self.detector
+                        .is_generated(&entry.path, &language, content.lines());
+                Some(file_stats)
+            });
+            match cached {
+                Some(file_stats) => snapshot.files.push(file_stats.clone()),
+                // Blobs that do not decode to text.
+                None => snapshot
+                    .skipped
+                    .push(SkippedFile::new(entry.path.clone(), SkipReason::Binary)),
+            }
+        }
+        snapshot.sorted()
+    }
+}
+
+/// Summarizes one sample per language, in language order. Generated and
+/// vendored files are left out.
+pub fn history_rows(sample: &Sample, stats: &[FileStats]) -> Vec<HistoryRow> {
+    let mut by_language: BTreeMap<&str, (usize, LangStats)> = BTreeMap::new();
+    for file in stats.iter().filter(|file| !file.generated) {
+        let (files, total) = by_language.entry(&file.language).or_default();
+        *files += 1;
+        total.pure_added += file.lang_stats.pure_added;
+        total.comment_lines_added += file.lang_stats.comment_lines_added;
+        total.docstring_lines_added += file.lang_stats.docstring_lines_added;
+        total.blank_lines_added += file.lang_stats.blank_lines_added;
+    }
+
+    by_language
+        .into_iter()
+        .map(|(language, (files, total))| {
+            let non_blank =
+                total.pure_added + total.comment_lines_added + total.docstring_lines_added;
+            let density = |lines: i64| {
+                if non_blank > 0 {
+                    lines as f64 / non_blank as f64
+                } else {
+                    0.0
+                }
+            };
+            HistoryRow {
+                date: sample.date.clone(),
+                sha: sample.commit.sha.clone(),
+                language: language.to_string(),
+                files,
+                pure_lines: total.pure_added,
+                comment_lines: total.comment_lines_added,
+                docstring_lines: total.docstring_lines_added,
+                blank_lines: total.blank_lines_added,
+                comment_density: density(total.comment_lines_added),
+                docstring_density: density(total.docstring_lines_added),
+            }
+        })
+        .collect()
+}
+
+/// Parses a `YYYY-MM-DD` date as midnight UTC, in seconds since the epoch.
+pub fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.trim().splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * SECONDS_PER_DAY)
+}
+
+/// Formats days since the epoch as `YYYY-MM-DD`.
+fn format_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+// Gregorian calendar conversions after Howard Hinnant's `days_from_civil`
+// and `civil_from_days`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(sha: &str, date: &str, hour: i64) -> CommitInfo {
+        CommitInfo {
+            sha: sha.to_string(),
+            author: "Test".to_string(),
+            subject: sha.to_string(),
+            time: parse_date(date).unwrap() + hour * 3600,
+        }
+    }
+
+    struct FakeBlobs(HashMap<&'static str, &'static str>);
+
+    impl BlobSource for FakeBlobs {
+        fn read_blob(&mut self, id: &str) -> Option<String> {
+            self.0.get(id).map(|content| content.to_string())
+        }
+    }
+
+    fn entry(path: &str, id: &str) -> TreeEntry {
+        TreeEntry {
+            path: path.to_string(),
+            id: id.to_string(),
+            size: 100,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_flags_generated_files() {
+        let mut blobs = FakeBlobs(HashMap::from([
+            (
+                "a1",
+                "// Code generated by protoc-gen-go. DO NOT EDIT.\npackage api\n",
+            ),
+            ("b2", "package main\n"),
+        ]));
+        let tree = [
+            entry("api/api.go", "a1"),
+            entry("api/api.pb.go", "a1"),
+            entry("main.go", "b2"),
+            entry("vendor/lib/lib.go", "b2"),
+        ];
+        let mut snapshotter = Snapshotter::new(
+            PathFilter::new(&["**/*".to_string()], &[]),
+            GeneratedDetector::default(),
+        );
+        let stats = snapshotter.snapshot(&tree, &mut blobs).files;
+        let generated: Vec<(&str, bool)> = stats
+            .iter()
+            .map(|fs| (fs.path.as_str(), fs.generated))
+            .collect();
+        assert_eq!(
+            generated,
+            [
+                ("api/api.go", true),
+                ("api/api.pb.go", true),
+                ("main.go", false),
+                ("vendor/lib/lib.go", true),
+            ]
+        );
+
+        let sample = Sample {
+            date: "2024-03-11".to_string(),
+            commit: commit("a", "2024-03-11", 9),
+        };
+        let rows = history_rows(&sample, &stats);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].files, 1);
+        assert_eq!(rows[0].pure_lines, 1);
+    }
+
+    #[test]
+    fn test_snapshot_reports_skipped_files() {
+        let mut blobs = FakeBlobs(HashMap::from([("a1", "x = 1\n")]));
+        let mut large = entry("data/big.py", "a1");
+        large.size = 2048;
+        let tree = [
+            entry("app.py", "a1"),
+            entry("docs/conf.py", "a1"),
+            entry("logo.png", "c3"),
+            large,
+        ];
+        let mut snapshotter = Snapshotter::new(
+            PathFilter::new(&["**/*".to_string()], &["docs/**".to_string()]),
+            GeneratedDetector::default(),
+        )
+        .with_max_file_size(1024);
+        let snapshot = snapshotter.snapshot(&tree, &mut blobs);
+        assert_eq!(snapshot.files.len(), 1);
+        assert_eq!(
+            snapshot.skipped,
+            [
+                SkippedFile::new("data/big.py", SkipReason::TooLarge),
+                SkippedFile::new("docs/conf.py", SkipReason::Excluded),
+                SkippedFile::new("logo.png", SkipReason::Binary),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dates_round_trip() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2024-02-29"), Some(1_709_164_800));
+        assert_eq!(format_date(1_709_164_800 / SECONDS_PER_DAY), "2024-02-29");
+        assert_eq!(format_date(-1), "1969-12-31");
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("last week"), None);
+    }
+
+    #[test]
+    fn test_sampling_keeps_newest_commit_per_period() {
+        // Newest first, as `git log --first-parent` lists them.
+        let commits = vec![
+            commit("e", "2024-03-13", 9),
+            commit("d", "2024-03-11", 18),
+            commit("c", "2024-03-11", 8),
+            commit("b", "2024-03-08", 12),
+            commit("a", "2024-02-01", 12),
+        ];
+        let since = parse_date("2024-03-01");
+
+        let shas = |samples: Vec<Sample>| -> Vec<(String, String)> {
+            samples
+                .into_iter()
+                .map(|s| (s.date, s.commit.sha))
+                .collect()
+        };
+        let by_day = shas(sample_commits(commits.clone(), since, Step::Day));
+        let expected: Vec<(String, String)> = [
+            ("2024-03-08", "b"),
+            ("2024-03-11", "d"),
+            ("2024-03-13", "e"),
+        ]
+        .iter()
+        .map(|(d, s)| (d.to_string(), s.to_string()))
+        .collect();
+        assert_eq!(by_day, expected);
+
+        // 2024-03-11 is a Monday.
+        let by_week = shas(sample_commits(commits.clone(), since, Step::Week));
+        let expected: Vec<(String, String)> = [("2024-03-04", "b"), ("2024-03-11", "e")]
+            .iter()
+            .map(|(d, s)| (d.to_string(), s.to_string()))
+            .collect();
+        assert_eq!(by_week, expected);
+
+        assert_eq!(sample_commits(commits, None, Step::Commit).len(), 5);
+    }
+
+    #[test]
+    fn test_since_skips_out_of_order_commits() {
+        // "b" was cherry-picked with its original, older committer date.
+        let commits = vec![
+            commit("d", "2024-03-13", 9),
+            commit("c", "2024-03-11", 18),
+            commit("b", "2024-01-15", 8),
+            commit("a", "2024-03-08", 12),
+        ];
+        let since = parse_date("2024-03-01");
+        let shas: Vec<String> = sample_commits(commits, since, Step::Commit)
+            .into_iter()
+            .map(|s| s.commit.sha)
+            .collect();
+        assert_eq!(shas, ["a", "c", "d"]);
+    }
+}
@@ -1,7 +1,9 @@
+use crate::config::CustomLanguage;
 use std::fmt;
 use std::path::Path;
+use std::sync::Arc;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Language {
     Python,
     JavaScript,
@@ -24,6 +26,13 @@ pub enum Language {
     Rust,
     Yaml,
     Toml,
+    Sql,
+    Lua,
+    Haskell,
+    Elm,
+    Ada,
+    /// A language declared in the config, which carries its own syntax.
+    Custom(Arc<CustomLanguage>),
     Other,
 }
 
@@ -51,6 +60,11 @@ impl Language {
             Some("rs") => Language::Rust,
             Some("yml") | Some("yaml") => Language::Yaml,
             Some("toml") => Language::Toml,
+            Some("sql") => Language::Sql,
+            Some("lua") => Language::Lua,
+            Some("hs") => Language::Haskell,
+            Some("elm") => Language::Elm,
+            Some("adb") | Some("ads") | Some("ada") => Language::Ada,
             _ => {
                 // Check filename for special cases
                 let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
@@ -66,6 +80,186 @@ impl Language {
             }
         }
     }
+
+    /// Looks up a language by its display name, as linguist spells it, or a
+    /// common alias, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let language = match name.to_ascii_lowercase().as_str() {
+            "python" | "python3" | "py" => Language::Python,
+            "javascript" | "js" | "node" | "jsx" => Language::JavaScript,
+            "typescript" | "ts" | "tsx" => Language::TypeScript,
+            "html" => Language::Html,
+            "css" | "scss" => Language::Css,
+            "c" => Language::C,
+            "c++" | "cpp" => Language::Cpp,
+            "c#" | "csharp" | "cs" => Language::Csharp,
+            "java" => Language::Java,
+            "go" | "golang" => Language::Go,
+            "php" => Language::Php,
+            "ruby" | "rb" => Language::Ruby,
+            "swift" => Language::Swift,
+            "kotlin" => Language::Kotlin,
+            "scala" => Language::Scala,
+            "shell" | "shell-script" | "sh" | "bash" | "zsh" | "dockerfile" | "makefile" => {
+                Language::Shell
+            }
+            "powershell" | "pwsh" | "ps1" => Language::PowerShell,
+            "vue" => Language::Vue,
+            "rust" | "rs" => Language::Rust,
+            "yaml" | "yml" => Language::Yaml,
+            "toml" => Language::Toml,
+            "sql" | "plsql" | "pgsql" => Language::Sql,
+            "lua" => Language::Lua,
+            "haskell" | "hs" => Language::Haskell,
+            "elm" => Language::Elm,
+            "ada" => Language::Ada,
+            "other" | "text" => Language::Other,
+            _ => return None,
+        };
+        Some(language)
+    }
+}
+
+/// The languages a run can detect: the built-in ones and those the config
+/// declares, which take precedence for the extensions, file names and names
+/// they list.
+#[derive(Debug, Clone, Default)]
+pub struct Languages {
+    custom: Vec<Arc<CustomLanguage>>,
+}
+
+impl Languages {
+    pub fn new(custom: Vec<CustomLanguage>) -> Self {
+        Self {
+            custom: custom.into_iter().map(Arc::new).collect(),
+        }
+    }
+
+    /// The languages declared in the config, in the order it lists them.
+    #[must_use]
+    pub fn custom(&self) -> &[Arc<CustomLanguage>] {
+        &self.custom
+    }
+
+    /// Detects by extension or file name, trying the declared languages first.
+    pub fn from_path(&self, path: &Path) -> Language {
+        self.custom_from_path(path)
+            .unwrap_or_else(|| Language::from_path(path))
+    }
+
+    /// Looks up a declared language by name, ignoring case, then a built-in one.
+    pub fn from_name(&self, name: &str) -> Option<Language> {
+        self.custom
+            .iter()
+            .find(|custom| custom.name.eq_ignore_ascii_case(name))
+            .map(|custom| Language::Custom(Arc::clone(custom)))
+            .or_else(|| Language::from_name(name))
+    }
+
+    fn custom_from_path(&self, path: &Path) -> Option<Language> {
+        let name = path.file_name()?.to_str()?;
+        let has_extension = |extension: &String| {
+            name.strip_suffix(extension.as_str())
+                .and_then(|stem| stem.strip_suffix('.'))
+                .is_some_and(|stem| !stem.is_empty())
+        };
+        self.custom
+            .iter()
+            .find(|custom| {
+                custom.filenames.iter().any(|filename| filename == name)
+                    || custom.extensions.iter().any(has_extension)
+            })
+            .map(|custom| Language::Custom(Arc::clone(custom)))
+    }
+
+    /// Detects by extension or file name and, for files those say nothing
+    /// about, by the first line of their content.
+    pub fn detect(&self, path: &Path, first_line: Option<&str>) -> Language {
+        match self.from_path(path) {
+            Language::Other => first_line
+                .and_then(|line| self.from_first_line(line))
+                .unwrap_or(Language::Other),
+            language => language,
+        }
+    }
+
+    /// Reads a `#!` interpreter line or a Vim or Emacs modeline.
+    pub fn from_first_line(&self, line: &str) -> Option<Language> {
+        from_shebang(line)
+            .or_else(|| vim_modeline(line).and_then(|name| self.from_name(name)))
+            .or_else(|| emacs_modeline(line).and_then(|name| self.from_name(name)))
+    }
+}
+
+fn from_shebang(line: &str) -> Option<Language> {
+    let mut args = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = program_name(args.next()?);
+    if program == "env" {
+        // Skip the options and variable assignments of env, including the
+        // `-S` that lets a shebang pass arguments.
+        program = loop {
+            match args.next()? {
+                "-u" | "--unset" => {
+                    args.next();
+                }
+                arg if arg.starts_with('-') || arg.contains('=') => {}
+                arg => break program_name(arg),
+            }
+        };
+    }
+    // Versioned interpreters such as python3.12 or ruby2.7.
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let language = match program {
+        "python" | "pypy" => Language::Python,
+        "node" | "nodejs" | "deno" | "bun" => Language::JavaScript,
+        "ts-node" => Language::TypeScript,
+        "sh" | "bash" | "zsh" | "dash" | "ksh" | "ash" => Language::Shell,
+        "ruby" | "jruby" => Language::Ruby,
+        "php" => Language::Php,
+        "pwsh" => Language::PowerShell,
+        "scala" => Language::Scala,
+        "lua" | "luajit" => Language::Lua,
+        "runghc" | "runhaskell" => Language::Haskell,
+        _ => return None,
+    };
+    Some(language)
+}
+
+fn program_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// The file type of `vim: set ft=python:`, `vi: filetype=sh` and the like.
+fn vim_modeline(line: &str) -> Option<&str> {
+    let options = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        let at = line.find(marker)?;
+        let starts_word = line[..at]
+            .chars()
+            .next_back()
+            .is_none_or(char::is_whitespace);
+        starts_word.then(|| &line[at + marker.len()..])
+    })?;
+    options
+        .split(|c: char| c == ':' || c.is_whitespace())
+        .filter_map(|option| option.split_once('='))
+        .find(|(name, _)| matches!(*name, "ft" | "filetype" | "syn" | "syntax"))
+        .map(|(_, value)| value)
+}
+
+/// The mode of `-*- mode: python -*-` or the short form `-*- python -*-`.
+fn emacs_modeline(line: &str) -> Option<&str> {
+    let (_, rest) = line.split_once("-*-")?;
+    let (variables, _) = rest.split_once("-*-")?;
+    let mode = if variables.contains(':') {
+        variables.split(';').find_map(|variable| {
+            let (name, value) = variable.split_once(':')?;
+            name.trim().eq_ignore_ascii_case("mode").then_some(value)
+        })?
+    } else {
+        variables
+    };
+    let mode = mode.trim();
+    Some(mode.strip_suffix("-mode").unwrap_or(mode))
 }
 
 impl fmt::Display for Language {
@@ -92,8 +286,101 @@ impl fmt::Display for Language {
             Language::Rust => "Rust",
             Language::Yaml => "YAML",
             Language::Toml => "TOML",
+            Language::Sql => "SQL",
+            Language::Lua => "Lua",
+            Language::Haskell => "Haskell",
+            Language::Elm => "Elm",
+            Language::Ada => "Ada",
+            Language::Custom(custom) => custom.name.as_str(),
             Language::Other => "Other",
         };
         write!(f, "{}", s)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shebangs() {
+        let languages = Languages::default();
+        let detect = |line| languages.detect(Path::new("bin/tool"), Some(line));
+        assert_eq!(detect("#!/usr/bin/python3"), Language::Python);
+        assert_eq!(detect("#!/usr/bin/env python3.12 -u"), Language::Python);
+        assert_eq!(detect("#!/bin/bash -e"), Language::Shell);
+        assert_eq!(
+            detect("#!/usr/bin/env -S node --no-warnings"),
+            Language::JavaScript
+        );
+        assert_eq!(detect("#!/usr/bin/env -u HOME ruby"), Language::Ruby);
+        assert_eq!(detect("#!/usr/bin/perl"), Language::Other);
+        assert_eq!(detect("echo hi"), Language::Other);
+        // The extension wins over the content.
+        assert_eq!(
+            languages.detect(Path::new("setup.rb"), Some("#!/bin/sh")),
+            Language::Ruby
+        );
+    }
+
+    #[test]
+    fn test_modelines() {
+        let languages = Languages::default();
+        let first_line = |line| languages.from_first_line(line);
+        assert_eq!(first_line("# vim: set ft=python:"), Some(Language::Python));
+        assert_eq!(
+            first_line("// vi: ts=4 filetype=javascript"),
+            Some(Language::JavaScript)
+        );
+        assert_eq!(
+            first_line("# -*- mode: shell-script; coding: utf-8 -*-"),
+            Some(Language::Shell)
+        );
+        assert_eq!(first_line("; -*- ruby -*-"), Some(Language::Ruby));
+        assert_eq!(first_line("# nvim:ft=python"), None);
+        assert_eq!(first_line("# -*- coding: utf-8 -*-"), None);
+    }
+
+    #[test]
+    fn test_custom_languages() {
+        let elixir = CustomLanguage {
+            name: "Elixir".to_string(),
+            extensions: vec!["ex".to_string(), "exs".to_string()],
+            filenames: vec!["mix.lock".to_string()],
+            ..CustomLanguage::default()
+        };
+        let terraform = CustomLanguage {
+            name: "Terraform".to_string(),
+            extensions: vec!["tf.json".to_string(), "py".to_string()],
+            ..CustomLanguage::default()
+        };
+        let languages = Languages::new(vec![elixir, terraform]);
+        let name = |path| languages.from_path(Path::new(path)).to_string();
+
+        assert_eq!(name("lib/app.ex"), "Elixir");
+        assert_eq!(name("mix.lock"), "Elixir");
+        assert_eq!(name("main.tf.json"), "Terraform");
+        // Declared extensions take precedence over built-in ones.
+        assert_eq!(name("setup.py"), "Terraform");
+        // A bare extension is not a file name.
+        assert_eq!(name("ex"), "Other");
+        assert_eq!(name("src/lib.rs"), "Rust");
+
+        assert_eq!(
+            languages.from_name("elixir"),
+            Some(Language::Custom(Arc::clone(&languages.custom()[0])))
+        );
+        assert_eq!(
+            languages
+                .from_first_line("# vim: ft=elixir")
+                .map(|l| l.to_string()),
+            Some("Elixir".to_string())
+        );
+        // Without the config, the same names and paths fall back to the built-in languages.
+        assert_eq!(Languages::default().from_name("elixir"), None);
+        assert_eq!(
+            Languages::default().from_path(Path::new("lib/app.ex")),
+            Language::Other
+        );
+    }
+}
@@ -1,13 +1,24 @@
+pub mod attributes;
+pub mod authors;
+pub mod cache;
 pub mod classifier;
 pub mod config;
 pub mod diff;
+pub mod encoding;
 pub mod files;
+pub mod generated;
+pub mod history;
 pub mod language;
+#[cfg(feature = "git2")]
+pub mod libgit;
+pub mod moved;
 pub mod parser;
+pub mod reformat;
 pub mod report;
 pub mod stats;
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests;
 
 pub fn detect_language(path: &str) -> String {
@@ -0,0 +1,656 @@
+//! In-process git access through libgit2, used instead of the `git` binary when
+//! purecode is built with the `git2` feature.
+
+use crate::authors::BlamedLine;
+use crate::diff::{self, LocalChanges};
+use crate::encoding;
+use crate::generated::GeneratedDetector;
+use crate::history::TreeEntry;
+use crate::language::Languages;
+use crate::parser::{BlobSource, DiffBuilder, HunkHeader};
+use crate::stats::{CommitInfo, FileStats, FileStatus, SkippedFile};
+use git2::{
+    BlameOptions, Commit, Delta, DiffFile, DiffFindOptions, DiffOptions, ErrorCode, Mailmap, Oid,
+    Patch,
+};
+use git2::{
+    ObjectType, Repository, Sort, Status, StatusOptions, Tree, TreeWalkMode, TreeWalkResult,
+};
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum GitError {
+    /// No repository was found in or above the directory.
+    NotARepository(git2::Error),
+    /// A ref or revision expression did not resolve to a commit.
+    BadRevision {
+        name: String,
+        source: git2::Error,
+    },
+    /// The two commits share no history.
+    NoMergeBase {
+        base: String,
+        head: String,
+    },
+    Git(git2::Error),
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitError::NotARepository(e) => write!(f, "not a git repository: {}", e.message()),
+            GitError::BadRevision { name, source } => {
+                write!(f, "unknown revision '{name}': {}", source.message())
+            }
+            GitError::NoMergeBase { base, head } => {
+                write!(f, "no merge base between '{base}' and '{head}'")
+            }
+            GitError::Git(e) => write!(f, "{}", e.message()),
+        }
+    }
+}
+
+impl std::error::Error for GitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GitError::NotARepository(e) | GitError::Git(e) => Some(e),
+            GitError::BadRevision { source, .. } => Some(source),
+            GitError::NoMergeBase { .. } => None,
+        }
+    }
+}
+
+impl From<git2::Error> for GitError {
+    fn from(e: git2::Error) -> Self {
+        GitError::Git(e)
+    }
+}
+
+/// Opens the repository containing `path`, searching parent directories.
+pub fn open_repository(path: &Path) -> Result<Repository, GitError> {
+    Repository::discover(path).map_err(GitError::NotARepository)
+}
+
+/// Resolves a ref or revision expression such as `origin/main` or `HEAD~2` to a commit.
+pub fn resolve_commit<'r>(repo: &'r Repository, name: &str) -> Result<Commit<'r>, GitError> {
+    repo.revparse_single(name)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|source| GitError::BadRevision {
+            name: name.to_string(),
+            source,
+        })
+}
+
+/// Diffs `head` against its merge base with `base`, like `git diff base...head`.
+pub fn diff_range(
+    repo: &Repository,
+    base: &str,
+    head: &str,
+    stats: &mut Vec<FileStats>,
+    languages: &Languages,
+) -> Result<Vec<SkippedFile>, GitError> {
+    let base_commit = resolve_commit(repo, base)?;
+    let head_commit = resolve_commit(repo, head)?;
+    let merge_base = repo
+        .merge_base(base_commit.id(), head_commit.id())
+        .map_err(|e| match e.code() {
+            ErrorCode::NotFound => GitError::NoMergeBase {
+                base: base.to_string(),
+                head: head.to_string(),
+            },
+            _ => GitError::Git(e),
+        })?;
+    let old_tree = repo.find_commit(merge_base)?.tree()?;
+    diff_trees(
+        repo,
+        Some(&old_tree),
+        Some(&head_commit.tree()?),
+        stats,
+        languages,
+    )
+}
+
+/// Lists the non-merge commits reachable from `head` but not from `base`, oldest first.
+pub fn list_commits(
+    repo: &Repository,
+    base: &str,
+    head: &str,
+) -> Result<Vec<CommitInfo>, GitError> {
+    let base_commit = resolve_commit(repo, base)?;
+    let head_commit = resolve_commit(repo, head)?;
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    walk.push(head_commit.id())?;
+    walk.hide(base_commit.id())?;
+
+    let mailmap = repo.mailmap()?;
+    let mut commits = Vec::new();
+    for id in walk {
+        let commit = repo.find_commit(id?)?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        commits.push(commit_info(&commit, &mailmap));
+    }
+    Ok(commits)
+}
+
+/// Lists the first-parent history of `head`, newest first.
+pub fn list_first_parent(repo: &Repository, head: &str) -> Result<Vec<CommitInfo>, GitError> {
+    let head_commit = resolve_commit(repo, head)?;
+    let mut walk = repo.revwalk()?;
+    walk.simplify_first_parent()?;
+    walk.push(head_commit.id())?;
+
+    let mailmap = repo.mailmap()?;
+    let mut commits = Vec::new();
+    for id in walk {
+        commits.push(commit_info(&repo.find_commit(id?)?, &mailmap));
+    }
+    Ok(commits)
+}
+
+fn commit_info(commit: &Commit, mailmap: &Mailmap) -> CommitInfo {
+    let author = commit
+        .author_with_mailmap(mailmap)
+        .unwrap_or_else(|_| commit.author().to_owned());
+    CommitInfo {
+        sha: commit.id().to_string(),
+        author: author.name().unwrap_or_default().to_string(),
+        subject: commit.summary().unwrap_or_default().to_string(),
+        time: commit.time().seconds(),
+    }
+}
+
+/// Blames every line of a file at `rev`, with author names after mailmap.
+/// Returns `None` for binary files.
+pub fn blame_file(
+    repo: &Repository,
+    rev: &str,
+    path: &str,
+) -> Result<Option<Vec<BlamedLine>>, GitError> {
+    let commit = resolve_commit(repo, rev)?;
+    let entry = commit.tree()?.get_path(Path::new(path))?;
+    let blob = repo.find_blob(entry.id())?;
+    let Some(content) = encoding::decode_text(blob.content()) else {
+        return Ok(None);
+    };
+    let mut options = BlameOptions::new();
+    options.newest_commit(commit.id()).use_mailmap(true);
+    let blame = repo.blame_file(Path::new(path), Some(&mut options))?;
+
+    Ok(Some(
+        content
+            .lines()
+            .enumerate()
+            .map(|(i, line)| BlamedLine {
+                author: blame
+                    .get_line(i + 1)
+                    .and_then(|hunk| hunk.final_signature().name().map(str::to_string))
+                    .unwrap_or_default(),
+                content: line.to_string(),
+            })
+            .collect(),
+    ))
+}
+
+/// Lists the files of a commit's tree, skipping submodules and symlinks.
+pub fn list_tree(repo: &Repository, sha: &str) -> Result<Vec<TreeEntry>, GitError> {
+    let tree = resolve_commit(repo, sha)?.tree()?;
+    let odb = repo.odb()?;
+    let mut entries = Vec::new();
+    let mut error = None;
+    let walked = tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() == Some(ObjectType::Blob) && entry.filemode() != 0o120000 {
+            if let Some(name) = entry.name() {
+                // The header alone gives the size, without inflating the blob.
+                let size = match odb.read_header(entry.id()) {
+                    Ok((size, _)) => size as u64,
+                    Err(e) => {
+                        error = Some(e);
+                        return TreeWalkResult::Abort;
+                    }
+                };
+                entries.push(TreeEntry {
+                    path: format!("{dir}{name}"),
+                    id: entry.id().to_string(),
+                    size,
+                });
+            }
+        }
+        TreeWalkResult::Ok
+    });
+    // An aborted walk fails on its own; the header error says why.
+    if let Some(e) = error {
+        return Err(e.into());
+    }
+    walked?;
+    Ok(entries)
+}
+
+/// Diffs a commit against its first parent, or against the empty tree for a root commit.
+pub fn diff_commit(
+    repo: &Repository,
+    sha: &str,
+    stats: &mut Vec<FileStats>,
+    languages: &Languages,
+) -> Result<Vec<SkippedFile>, GitError> {
+    let commit = resolve_commit(repo, sha)?;
+    let parent_tree = match commit.parents().next() {
+        Some(parent) => Some(parent.tree()?),
+        None => None,
+    };
+    diff_trees(
+        repo,
+        parent_tree.as_ref(),
+        Some(&commit.tree()?),
+        stats,
+        languages,
+    )
+}
+
+/// Reads the attributes of the work tree; a bare repository has none.
+fn detector(repo: &Repository, languages: &Languages) -> GeneratedDetector {
+    repo.workdir()
+        .map(GeneratedDetector::discover)
+        .unwrap_or_default()
+        .with_languages(languages.clone())
+}
+
+/// Diffs two trees with rename and copy detection; a missing tree stands for an empty one.
+pub fn diff_trees(
+    repo: &Repository,
+    old_tree: Option<&Tree>,
+    new_tree: Option<&Tree>,
+    stats: &mut Vec<FileStats>,
+    languages: &Languages,
+) -> Result<Vec<SkippedFile>, GitError> {
+    let mut diff = repo.diff_tree_to_tree(old_tree, new_tree, Some(&mut diff_options()))?;
+    find_similar(&mut diff)?;
+
+    let mut blobs = RepoBlobSource::new(repo);
+    let mut builder =
+        DiffBuilder::new(stats, Some(&mut blobs)).with_detector(detector(repo, languages));
+    collect_diff(repo, &diff, &mut builder)?;
+    Ok(builder.finish())
+}
+
+/// Diffs uncommitted changes, optionally adding untracked files as wholly added.
+pub fn diff_local(
+    repo: &Repository,
+    changes: LocalChanges,
+    include_untracked: bool,
+    stats: &mut Vec<FileStats>,
+    languages: &Languages,
+) -> Result<Vec<SkippedFile>, GitError> {
+    let mut diff = match changes {
+        LocalChanges::Staged => {
+            // An unborn branch has no HEAD tree; everything staged is added.
+            let head = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+            repo.diff_tree_to_index(head.as_ref(), None, Some(&mut diff_options()))?
+        }
+        LocalChanges::Worktree => repo.diff_index_to_workdir(None, Some(&mut diff_options()))?,
+    };
+    find_similar(&mut diff)?;
+
+    let mut blobs = RepoBlobSource::new(repo);
+    let mut builder =
+        DiffBuilder::new(stats, Some(&mut blobs)).with_detector(detector(repo, languages));
+    let root = repo.workdir().map(Path::to_path_buf);
+    if let (LocalChanges::Worktree, Some(root)) = (changes, &root) {
+        builder = builder.with_worktree(root.clone());
+    }
+    collect_diff(repo, &diff, &mut builder)?;
+
+    if let (true, Some(root)) = (include_untracked, &root) {
+        for path in untracked_paths(repo)? {
+            match diff::read_untracked(root, &path) {
+                Ok(content) => builder.add_file(&path, &content),
+                Err(reason) => builder.skip_file(&path, reason),
+            }
+        }
+    }
+    Ok(builder.finish())
+}
+
+fn diff_options() -> DiffOptions {
+    let mut options = DiffOptions::new();
+    options.context_lines(0);
+    options
+}
+
+fn find_similar(diff: &mut git2::Diff) -> Result<(), GitError> {
+    diff.find_similar(Some(DiffFindOptions::new().renames(true).copies(true)))?;
+    Ok(())
+}
+
+/// Lists untracked, non-ignored files relative to the top of the working tree.
+fn untracked_paths(repo: &Repository) -> Result<Vec<String>, GitError> {
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .exclude_submodules(true);
+    let statuses = repo.statuses(Some(&mut options))?;
+    Ok(statuses
+        .iter()
+        .filter(|entry| entry.status().contains(Status::WT_NEW))
+        .filter_map(|entry| entry.path().map(str::to_string))
+        .collect())
+}
+
+/// Feeds every delta of `diff` through `builder`, reading hunks and blobs
+/// straight from the object database.
+pub fn collect_diff(
+    repo: &Repository,
+    diff: &git2::Diff,
+    builder: &mut DiffBuilder,
+) -> Result<(), GitError> {
+    for (idx, delta) in diff.deltas().enumerate() {
+        let status = match delta.status() {
+            Delta::Added | Delta::Untracked => FileStatus::Added,
+            Delta::Deleted => FileStatus::Deleted,
+            Delta::Renamed => FileStatus::Renamed,
+            Delta::Copied => FileStatus::Copied,
+            _ => FileStatus::Modified,
+        };
+        let old_path = file_path(&delta.old_file()).or_else(|| file_path(&delta.new_file()));
+        let new_path = file_path(&delta.new_file()).or_else(|| old_path.clone());
+        let (Some(old_path), Some(new_path)) = (old_path, new_path) else {
+            continue;
+        };
+        builder.start_file(&old_path, &new_path, status);
+        builder.set_blob_ids(blob_id(&delta.old_file()), blob_id(&delta.new_file()));
+
+        let patch = Patch::from_diff(diff, idx)?;
+        let is_binary = match &patch {
+            Some(patch) => patch.delta().flags().is_binary(),
+            None => delta.flags().is_binary(),
+        };
+        if is_binary {
+            builder.mark_binary();
+            continue;
+        }
+        let Some(patch) = patch else {
+            continue;
+        };
+        if matches!(status, FileStatus::Renamed | FileStatus::Copied) {
+            let similarity = similarity(repo, &delta.old_file(), &delta.new_file(), &patch);
+            if let Some(fs) = builder.file_mut() {
+                fs.similarity = similarity;
+            }
+        }
+
+        for h in 0..patch.num_hunks() {
+            let (hunk, lines) = patch.hunk(h)?;
+            builder.start_hunk(Some(HunkHeader {
+                old_start: hunk.old_start() as usize,
+                old_count: hunk.old_lines() as usize,
+                new_start: hunk.new_start() as usize,
+                new_count: hunk.new_lines() as usize,
+            }));
+            for l in 0..lines {
+                let line = patch.line_in_hunk(h, l)?;
+                let content = String::from_utf8_lossy(line.content());
+                let content = content.strip_suffix('\n').unwrap_or(&content);
+                let content = content.strip_suffix('\r').unwrap_or(content);
+                match line.origin() {
+                    '+' => builder.added(content),
+                    '-' => builder.removed(content),
+                    ' ' => builder.context(content),
+                    // End-of-file newline markers carry no content.
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn file_path(file: &DiffFile) -> Option<String> {
+    file.path().map(|p| p.to_string_lossy().into_owned())
+}
+
+fn blob_id(file: &DiffFile) -> Option<String> {
+    let id = file.id();
+    (!id.is_zero()).then(|| id.to_string())
+}
+
+/// Estimates the similarity index of a rename or copy as the share of lines the
+/// two sides have in common. libgit2 computes git's byte-based score but does
+/// not expose it.
+fn similarity(
+    repo: &Repository,
+    old_file: &DiffFile,
+    new_file: &DiffFile,
+    patch: &Patch,
+) -> Option<u8> {
+    if old_file.id() == new_file.id() {
+        return Some(100);
+    }
+    let (_, _, deletions) = patch.line_stats().ok()?;
+    let old_lines = line_count(repo, old_file.id())?;
+    let new_lines = line_count(repo, new_file.id())?;
+    let total = old_lines.max(new_lines);
+    if total == 0 {
+        return Some(100);
+    }
+    Some((old_lines.saturating_sub(deletions) * 100 / total) as u8)
+}
+
+fn line_count(repo: &Repository, id: Oid) -> Option<usize> {
+    let blob = repo.find_blob(id).ok()?;
+    let content = blob.content();
+    Some(content.split(|&b| b == b'\n').count() - usize::from(content.ends_with(b"\n")))
+}
+
+/// Reads blobs from the object database of an open repository.
+pub struct RepoBlobSource<'r> {
+    repo: &'r Repository,
+}
+
+impl<'r> RepoBlobSource<'r> {
+    pub fn new(repo: &'r Repository) -> Self {
+        Self { repo }
+    }
+}
+
+impl BlobSource for RepoBlobSource<'_> {
+    fn read_blob(&mut self, id: &str) -> Option<String> {
+        let blob = self.repo.find_blob(Oid::from_str(id).ok()?).ok()?;
+        encoding::decode_text(blob.content())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// A throwaway repository under the system temp directory.
+    struct TempRepo {
+        dir: PathBuf,
+        repo: Repository,
+    }
+
+    impl TempRepo {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("purecode-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            let repo = Repository::init(&dir).unwrap();
+            Self { dir, repo }
+        }
+
+        fn commit(&self, files: &[(&str, &str)], message: &str) -> Oid {
+            let mut index = self.repo.index().unwrap();
+            for (path, content) in files {
+                fs::write(self.dir.join(path), content).unwrap();
+                index.add_path(Path::new(path)).unwrap();
+            }
+            index.write().unwrap();
+            let tree = self.repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let signature = Signature::now("Test", "test@example.com").unwrap();
+            let parent = self.repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+            let parents: Vec<&Commit> = parent.iter().collect();
+            self.repo
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    message,
+                    &tree,
+                    &parents,
+                )
+                .unwrap()
+        }
+    }
+
+    impl Drop for TempRepo {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn test_diff_range_in_process() {
+        let repo = TempRepo::new("diff-range");
+        let base = repo.commit(&[("lib.rs", "/*\n  doc\n*/\nfn a() {}\n")], "base");
+        repo.commit(
+            &[(
+                "lib.rs",
+                "/*\n  doc\n  more doc\n*/\nfn a() {}\nfn b() {}\n",
+            )],
+            "head",
+        );
+
+        let mut stats = Vec::new();
+        diff_range(
+            &repo.repo,
+            &base.to_string(),
+            "HEAD",
+            &mut stats,
+            &Languages::default(),
+        )
+        .unwrap();
+        assert_eq!(stats.len(), 1);
+        let s = &stats[0].lang_stats;
+        // The added comment line sits between hunks and is only known to be
+        // inside a block comment through blob replay.
+        assert_eq!(s.comment_lines_added, 1);
+        assert_eq!(s.pure_added, 1);
+        assert_eq!(stats[0].status, FileStatus::Modified);
+    }
+
+    #[test]
+    fn test_bad_revision_is_reported() {
+        let repo = TempRepo::new("bad-rev");
+        repo.commit(&[("a.py", "x = 1\n")], "base");
+
+        let mut stats = Vec::new();
+        let err = diff_range(
+            &repo.repo,
+            "no-such-branch",
+            "HEAD",
+            &mut stats,
+            &Languages::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, GitError::BadRevision { ref name, .. } if name == "no-such-branch"));
+    }
+
+    #[test]
+    fn test_staged_and_untracked_changes() {
+        let repo = TempRepo::new("local");
+        repo.commit(&[("lib.rs", "fn a() {}\n")], "base");
+        fs::write(repo.dir.join("staged.rs"), "// note\nfn b() {}\n").unwrap();
+        let mut index = repo.repo.index().unwrap();
+        index.add_path(Path::new("staged.rs")).unwrap();
+        index.write().unwrap();
+        fs::write(repo.dir.join("notes.py"), "x = 1\n").unwrap();
+
+        let mut stats = Vec::new();
+        diff_local(
+            &repo.repo,
+            LocalChanges::Staged,
+            false,
+            &mut stats,
+            &Languages::default(),
+        )
+        .unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].path, "staged.rs");
+        assert_eq!(stats[0].lang_stats.comment_lines_added, 1);
+
+        let mut stats = Vec::new();
+        diff_local(
+            &repo.repo,
+            LocalChanges::Worktree,
+            true,
+            &mut stats,
+            &Languages::default(),
+        )
+        .unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].path, "notes.py");
+        assert_eq!(stats[0].status, FileStatus::Added);
+        assert_eq!(stats[0].lang_stats.pure_added, 1);
+    }
+
+    #[test]
+    fn test_commits_are_diffed_one_by_one() {
+        let repo = TempRepo::new("log");
+        let base = repo.commit(&[("a.py", "x = 1\n")], "base");
+        repo.commit(&[("a.py", "x = 1\ny = 2\nz = 3\n")], "grow");
+        repo.commit(&[("b.py", "# note\n")], "note");
+
+        let commits = list_commits(&repo.repo, &base.to_string(), "HEAD").unwrap();
+        let subjects: Vec<&str> = commits.iter().map(|c| c.subject.as_str()).collect();
+        assert_eq!(subjects, ["grow", "note"]);
+        assert_eq!(commits[0].author, "Test");
+
+        let mut stats = Vec::new();
+        diff_commit(
+            &repo.repo,
+            &commits[0].sha,
+            &mut stats,
+            &Languages::default(),
+        )
+        .unwrap();
+        assert_eq!(stats[0].lang_stats.pure_added, 2);
+
+        // The root commit is diffed against the empty tree.
+        let mut stats = Vec::new();
+        diff_commit(
+            &repo.repo,
+            &base.to_string(),
+            &mut stats,
+            &Languages::default(),
+        )
+        .unwrap();
+        assert_eq!(stats[0].status, FileStatus::Added);
+        assert_eq!(stats[0].lang_stats.pure_added, 1);
+    }
+
+    #[test]
+    fn test_list_tree_at_revision() {
+        let repo = TempRepo::new("tree");
+        fs::create_dir_all(repo.dir.join("src")).unwrap();
+        let first = repo.commit(&[("src/a.rs", "fn a() {}\n")], "first");
+        repo.commit(&[("b.py", "x = 1\n")], "second");
+
+        let paths = |rev: &str| -> Vec<String> {
+            let mut paths: Vec<String> = list_tree(&repo.repo, rev)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.path)
+                .collect();
+            paths.sort();
+            paths
+        };
+        assert_eq!(paths("HEAD"), ["b.py", "src/a.rs"]);
+        assert_eq!(paths(&first.to_string()), ["src/a.rs"]);
+    }
+}
@@ -1,7 +1,10 @@
 use clap::{Parser, Subcommand, ValueEnum};
 use purecode::{
-    config, diff, files, parser, report,
-    stats::{self, FileStats, ThresholdError},
+    attributes, authors, cache, config, diff, files, generated, history, language, parser, report,
+    stats::{
+        self, AuthorStats, CommitInfo, CommitStats, FileStats, HistoryRow, SkipReason, SkippedFile,
+        ThresholdError,
+    },
 };
 use std::io::BufReader;
 use std::process::ExitCode;
@@ -48,6 +51,14 @@ struct Cli {
     #[arg(long)]
     fail_on_decrease: bool,
 
+    /// Leave whitespace- and formatting-only changes out of net pure lines and complexity
+    #[arg(long)]
+    exclude_reformatted: bool,
+
+    /// Leave generated and vendored files out entirely instead of reporting them apart
+    #[arg(long)]
+    exclude_generated: bool,
+
     /// Only warn on threshold failures
     #[arg(long)]
     warn_only: bool,
@@ -73,6 +84,18 @@ enum Commands {
         #[arg(long)]
         stdin: bool,
 
+        /// Diff staged changes against HEAD instead of a commit range
+        #[arg(long, conflicts_with_all = ["base", "head", "stdin", "worktree"])]
+        staged: bool,
+
+        /// Diff unstaged changes in the working tree instead of a commit range
+        #[arg(long, conflicts_with_all = ["base", "head", "stdin"])]
+        worktree: bool,
+
+        /// Count untracked files as wholly added (implies --worktree unless --staged)
+        #[arg(long, conflicts_with_all = ["base", "head", "stdin"])]
+        include_untracked: bool,
+
         /// Output format
         #[arg(long, value_enum)]
         format: Option<Format>,
@@ -93,6 +116,14 @@ enum Commands {
         #[arg(long)]
         fail_on_decrease: bool,
 
+        /// Leave whitespace- and formatting-only changes out of net pure lines and complexity
+        #[arg(long)]
+        exclude_reformatted: bool,
+
+        /// Leave generated and vendored files out entirely instead of reporting them apart
+        #[arg(long)]
+        exclude_generated: bool,
+
         /// Only warn on threshold failures
         #[arg(long)]
         warn_only: bool,
@@ -101,6 +132,82 @@ enum Commands {
         #[arg(long)]
         ci: bool,
     },
+    /// Break down a ref range commit by commit
+    Log {
+        /// Base ref; commits reachable from it are left out
+        #[arg(long)]
+        base: Option<String>,
+
+        /// Head ref
+        #[arg(long, default_value = "HEAD")]
+        head: String,
+
+        /// Output format
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+
+        /// Leave whitespace- and formatting-only changes out of net pure lines and complexity
+        #[arg(long)]
+        exclude_reformatted: bool,
+
+        /// CI mode
+        #[arg(long)]
+        ci: bool,
+    },
+    /// Attribute pure, comment and docstring lines to their authors
+    Authors {
+        /// Paths to attribute in blame mode, relative to the top of the repository
+        #[arg(default_value = ".")]
+        paths: Vec<String>,
+
+        /// Credit each line of the snapshot at --rev to its blame author instead of
+        /// crediting commit diffs of --base..--head to their commit author
+        #[arg(long, conflicts_with = "base")]
+        blame: bool,
+
+        /// Revision whose snapshot is blamed
+        #[arg(long, default_value = "HEAD", requires = "blame")]
+        rev: String,
+
+        /// Base ref; commits reachable from it are left out
+        #[arg(long)]
+        base: Option<String>,
+
+        /// Head ref
+        #[arg(long, default_value = "HEAD")]
+        head: String,
+
+        /// Output format
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+
+        /// CI mode
+        #[arg(long)]
+        ci: bool,
+    },
+    /// Manage the cache of files mode results
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Track snapshot statistics over first-parent history
+    History {
+        /// Only sample commits from this date on (YYYY-MM-DD, UTC)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Sample every commit, or the last commit of each day or week
+        #[arg(long, value_enum, default_value = "commit")]
+        step: HistoryStep,
+
+        /// Ref whose history is sampled
+        #[arg(long, default_value = "HEAD")]
+        head: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value = "csv")]
+        format: HistoryFormat,
+    },
     /// Analyze files/directories (Snapshot mode)
     Files {
         /// Paths to include (defaults to all)
@@ -111,6 +218,26 @@ enum Commands {
         #[arg(long)]
         stdin: bool,
 
+        /// Analyze the tree at this git revision instead of the working directory
+        #[arg(long, conflicts_with = "stdin")]
+        rev: Option<String>,
+
+        /// Also count files skipped by .gitignore, .ignore and .purecodeignore
+        #[arg(long)]
+        no_ignore: bool,
+
+        /// Number of threads walking and classifying files (default: one per core)
+        #[arg(long, short = 'j')]
+        jobs: Option<usize>,
+
+        /// Skip files larger than this many bytes; 0 means no limit (default: 10 MiB)
+        #[arg(long)]
+        max_file_size: Option<u64>,
+
+        /// Classify every file again instead of reusing cached results
+        #[arg(long)]
+        no_cache: bool,
+
         /// Output format
         #[arg(long, value_enum)]
         format: Option<Format>,
@@ -131,6 +258,10 @@ enum Commands {
         #[arg(long)]
         fail_on_decrease: bool,
 
+        /// Leave generated and vendored files out entirely instead of reporting them apart
+        #[arg(long)]
+        exclude_generated: bool,
+
         /// Only warn on threshold failures
         #[arg(long)]
         warn_only: bool,
@@ -158,6 +289,35 @@ impl From<Format> for report::OutputFormat {
     }
 }
 
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    /// Delete cached results of files mode
+    Clear,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum HistoryStep {
+    Commit,
+    Day,
+    Week,
+}
+
+impl From<HistoryStep> for history::Step {
+    fn from(s: HistoryStep) -> Self {
+        match s {
+            HistoryStep::Commit => history::Step::Commit,
+            HistoryStep::Day => history::Step::Day,
+            HistoryStep::Week => history::Step::Week,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum HistoryFormat {
+    Csv,
+    Json,
+}
+
 fn resolve_format(cli_format: Option<Format>, config_format: &str) -> Format {
     cli_format.unwrap_or(match config_format {
         "json" => Format::Json,
@@ -166,38 +326,478 @@ fn resolve_format(cli_format: Option<Format>, config_format: &str) -> Format {
     })
 }
 
+/// The configured include globs; an empty list includes everything.
+fn include_patterns(config: &config::Config) -> Vec<String> {
+    if config.include.is_empty() {
+        vec!["**/*".to_string()]
+    } else {
+        config.include.clone()
+    }
+}
+
 struct FilesConfig {
     format: Format,
     per_file: bool,
     max_noise_ratio: Option<f64>,
     min_pure_lines: Option<i64>,
     fail_on_decrease: bool,
+    exclude_reformatted: bool,
+    exclude_generated: bool,
     warn_only: bool,
     ci: bool,
 }
 
+/// Where diff mode reads its changes from.
+enum DiffSource<'a> {
+    Stdin,
+    Range {
+        base: &'a str,
+        head: &'a str,
+    },
+    Local {
+        changes: diff::LocalChanges,
+        include_untracked: bool,
+    },
+}
+
+/// Changed files and the files that had to be skipped.
+type DiffStats = (Vec<FileStats>, Vec<SkippedFile>);
+
+/// Parses the diff from stdin or from git; git diffs replay classifier state from blobs.
+fn analyze_diff(
+    source: DiffSource,
+    languages: &language::Languages,
+) -> Result<DiffStats, Box<dyn std::error::Error>> {
+    let mut file_stats = Vec::new();
+    let skipped = match source {
+        DiffSource::Stdin => {
+            let mut builder = parser::DiffBuilder::new(&mut file_stats, None)
+                .with_detector(generated_detector(languages));
+            parser::parse_diff_into(diff::get_stdin_diff(), &mut builder)
+                .map_err(|e| format!("Error parsing diff: {e}"))?;
+            builder.finish()
+        }
+        DiffSource::Range { base, head } => diff_git_range(base, head, &mut file_stats, languages)?,
+        DiffSource::Local {
+            changes,
+            include_untracked,
+        } => diff_local_changes(changes, include_untracked, &mut file_stats, languages)?,
+    };
+    Ok((file_stats, skipped))
+}
+
+/// Diffs the range in process, without spawning git.
+#[cfg(feature = "git2")]
+fn diff_git_range(
+    base: &str,
+    head: &str,
+    file_stats: &mut Vec<FileStats>,
+    languages: &language::Languages,
+) -> Result<Vec<SkippedFile>, Box<dyn std::error::Error>> {
+    let repo = open_repository()?;
+    Ok(
+        purecode::libgit::diff_range(&repo, base, head, file_stats, languages)
+            .map_err(|e| format!("Error running git diff: {e}"))?,
+    )
+}
+
+/// Diffs uncommitted changes in process, without spawning git.
+#[cfg(feature = "git2")]
+fn diff_local_changes(
+    changes: diff::LocalChanges,
+    include_untracked: bool,
+    file_stats: &mut Vec<FileStats>,
+    languages: &language::Languages,
+) -> Result<Vec<SkippedFile>, Box<dyn std::error::Error>> {
+    let repo = open_repository()?;
+    Ok(
+        purecode::libgit::diff_local(&repo, changes, include_untracked, file_stats, languages)
+            .map_err(|e| format!("Error running git diff: {e}"))?,
+    )
+}
+
+#[cfg(feature = "git2")]
+fn open_repository() -> Result<git2::Repository, Box<dyn std::error::Error>> {
+    Ok(purecode::libgit::open_repository(std::path::Path::new("."))
+        .map_err(|e| format!("Error opening repository: {e}"))?)
+}
+
+/// Runs `git diff` and reads blobs through `git cat-file`.
+#[cfg(not(feature = "git2"))]
+fn diff_git_range(
+    base: &str,
+    head: &str,
+    file_stats: &mut Vec<FileStats>,
+    languages: &language::Languages,
+) -> Result<Vec<SkippedFile>, Box<dyn std::error::Error>> {
+    let reader =
+        diff::get_git_diff(base, head).map_err(|e| format!("Error running git diff: {e}"))?;
+    let mut blobs = diff::GitBlobSource::default();
+    let mut builder = parser::DiffBuilder::new(file_stats, Some(&mut blobs))
+        .with_detector(generated_detector(languages));
+    parser::parse_diff_into(reader, &mut builder)
+        .map_err(|e| format!("Error parsing diff: {e}"))?;
+    Ok(builder.finish())
+}
+
+/// Runs `git diff [--cached]` and reads untracked files from the working tree.
+#[cfg(not(feature = "git2"))]
+fn diff_local_changes(
+    changes: diff::LocalChanges,
+    include_untracked: bool,
+    file_stats: &mut Vec<FileStats>,
+    languages: &language::Languages,
+) -> Result<Vec<SkippedFile>, Box<dyn std::error::Error>> {
+    let reader =
+        diff::get_local_diff(changes).map_err(|e| format!("Error running git diff: {e}"))?;
+    let root = diff::repo_root().map_err(|e| format!("Error opening repository: {e}"))?;
+    let mut blobs = diff::GitBlobSource::default();
+    let mut builder = parser::DiffBuilder::new(file_stats, Some(&mut blobs))
+        .with_detector(generated_detector(languages));
+    if changes == diff::LocalChanges::Worktree {
+        builder = builder.with_worktree(root.clone());
+    }
+    parser::parse_diff_into(reader, &mut builder)
+        .map_err(|e| format!("Error parsing diff: {e}"))?;
+
+    if include_untracked {
+        let untracked =
+            diff::list_untracked().map_err(|e| format!("Error listing untracked files: {e}"))?;
+        for path in untracked {
+            match diff::read_untracked(&root, &path) {
+                Ok(content) => builder.add_file(&path, &content),
+                Err(reason) => builder.skip_file(&path, reason),
+            }
+        }
+    }
+    Ok(builder.finish())
+}
+
+/// Reads `.gitattributes` of the repository around the working directory.
+fn generated_detector(languages: &language::Languages) -> generated::GeneratedDetector {
+    std::env::current_dir()
+        .map(|cwd| generated::GeneratedDetector::discover(&cwd))
+        .unwrap_or_default()
+        .with_languages(languages.clone())
+}
+
+fn repo_attributes(languages: &language::Languages) -> attributes::GitAttributes {
+    std::env::current_dir()
+        .map(|cwd| attributes::GitAttributes::discover(&cwd))
+        .unwrap_or_default()
+        .with_languages(languages.clone())
+}
+
+/// Analyzes every non-merge commit of `base..head` on its own.
+fn analyze_log(
+    base: &str,
+    head: &str,
+    exclude_reformatted: bool,
+    languages: &language::Languages,
+) -> Result<Vec<CommitStats>, Box<dyn std::error::Error>> {
+    let mut commits = Vec::new();
+    for (commit, mut file_stats) in diff_commits(base, head, languages)? {
+        if exclude_reformatted {
+            stats::exclude_reformatted(&mut file_stats);
+        }
+        commits.push(CommitStats::new(commit, &file_stats));
+    }
+    Ok(commits)
+}
+
+/// A commit and the files it changed.
+type CommitDiff = (CommitInfo, Vec<FileStats>);
+
+/// Diffs each commit of the range against its first parent, in process.
+#[cfg(feature = "git2")]
+fn diff_commits(
+    base: &str,
+    head: &str,
+    languages: &language::Languages,
+) -> Result<Vec<CommitDiff>, Box<dyn std::error::Error>> {
+    let repo = open_repository()?;
+    let commits = purecode::libgit::list_commits(&repo, base, head)
+        .map_err(|e| format!("Error listing commits: {e}"))?;
+    let mut diffs = Vec::new();
+    for commit in commits {
+        let mut file_stats = Vec::new();
+        purecode::libgit::diff_commit(&repo, &commit.sha, &mut file_stats, languages)
+            .map_err(|e| format!("Error diffing commit {}: {e}", commit.sha))?;
+        diffs.push((commit, file_stats));
+    }
+    Ok(diffs)
+}
+
+/// Diffs each commit of the range against its first parent with `git diff-tree`.
+#[cfg(not(feature = "git2"))]
+fn diff_commits(
+    base: &str,
+    head: &str,
+    languages: &language::Languages,
+) -> Result<Vec<CommitDiff>, Box<dyn std::error::Error>> {
+    let commits =
+        diff::list_commits(base, head).map_err(|e| format!("Error listing commits: {e}"))?;
+    let mut blobs = diff::GitBlobSource::default();
+    let detector = generated_detector(languages);
+    let mut diffs = Vec::new();
+    for commit in commits {
+        let reader = diff::get_commit_diff(&commit.sha)
+            .map_err(|e| format!("Error diffing commit {}: {e}", commit.sha))?;
+        let mut file_stats = Vec::new();
+        let mut builder = parser::DiffBuilder::new(&mut file_stats, Some(&mut blobs))
+            .with_detector(detector.clone());
+        parser::parse_diff_into(reader, &mut builder)
+            .map_err(|e| format!("Error parsing diff: {e}"))?;
+        builder.finish();
+        diffs.push((commit, file_stats));
+    }
+    Ok(diffs)
+}
+
+/// Measures the tree at `rev` without checking it out. `paths` are relative to
+/// the top of the repository.
+fn analyze_revision(
+    rev: &str,
+    paths: &[String],
+    filter: files::PathFilter,
+    max_file_size: u64,
+    languages: &language::Languages,
+) -> Result<files::Snapshot, Box<dyn std::error::Error>> {
+    let mut snapshotter = history::Snapshotter::new(filter, generated_detector(languages))
+        .with_max_file_size(max_file_size);
+
+    #[cfg(feature = "git2")]
+    let snapshot = {
+        let repo = open_repository()?;
+        let mut tree = purecode::libgit::list_tree(&repo, rev)
+            .map_err(|e| format!("Error reading tree {rev}: {e}"))?;
+        tree.retain(|entry| files::is_under(&entry.path, paths));
+        snapshotter.snapshot(&tree, &mut purecode::libgit::RepoBlobSource::new(&repo))
+    };
+
+    #[cfg(not(feature = "git2"))]
+    let snapshot = {
+        let mut tree =
+            diff::list_tree(rev).map_err(|e| format!("Error reading tree {rev}: {e}"))?;
+        tree.retain(|entry| files::is_under(&entry.path, paths));
+        snapshotter.snapshot(&tree, &mut diff::GitBlobSource::default())
+    };
+
+    Ok(snapshot)
+}
+
+/// Credits every line of the snapshot at `rev` to the author who last changed it.
+fn analyze_blame(
+    rev: &str,
+    paths: &[String],
+    filter: &files::PathFilter,
+    languages: &language::Languages,
+) -> Result<Vec<AuthorStats>, Box<dyn std::error::Error>> {
+    let mut tally = authors::AuthorTally::new();
+    let attributes = repo_attributes(languages);
+
+    #[cfg(feature = "git2")]
+    {
+        let repo = open_repository()?;
+        let tree = purecode::libgit::list_tree(&repo, rev)
+            .map_err(|e| format!("Error reading tree {rev}: {e}"))?;
+        for entry in blamed_entries(&tree, paths, filter, &attributes) {
+            let Some(lines) = purecode::libgit::blame_file(&repo, rev, &entry.path)
+                .map_err(|e| format!("Error blaming {}: {e}", entry.path))?
+            else {
+                continue;
+            };
+            let first_line = lines.first().map(|line| line.content.as_str());
+            let language = attributes.language(&entry.path, first_line);
+            tally.add_blamed_file(&entry.path, &language, &lines);
+        }
+    }
+
+    #[cfg(not(feature = "git2"))]
+    {
+        let tree = diff::list_tree(rev).map_err(|e| format!("Error reading tree {rev}: {e}"))?;
+        let root = diff::repo_root().map_err(|e| format!("Error opening repository: {e}"))?;
+        for entry in blamed_entries(&tree, paths, filter, &attributes) {
+            let Some(lines) = diff::blame_file(&root, rev, &entry.path)
+                .map_err(|e| format!("Error blaming {}: {e}", entry.path))?
+            else {
+                continue;
+            };
+            let first_line = lines.first().map(|line| line.content.as_str());
+            let language = attributes.language(&entry.path, first_line);
+            tally.add_blamed_file(&entry.path, &language, &lines);
+        }
+    }
+
+    Ok(tally.finish())
+}
+
+/// The files of a tree that blame mode attributes; binary files are skipped
+/// by the blame itself.
+fn blamed_entries<'t>(
+    tree: &'t [history::TreeEntry],
+    paths: &[String],
+    filter: &files::PathFilter,
+    attributes: &attributes::GitAttributes,
+) -> Vec<&'t history::TreeEntry> {
+    tree.iter()
+        .filter(|entry| files::is_under(&entry.path, paths) && filter.matches(&entry.path))
+        .filter(|entry| attributes.exclusion(&entry.path).is_none())
+        .collect()
+}
+
+/// Credits the diff of every non-merge commit of `base..head` to its author.
+fn analyze_commit_authors(
+    base: &str,
+    head: &str,
+    languages: &language::Languages,
+) -> Result<Vec<AuthorStats>, Box<dyn std::error::Error>> {
+    let mut tally = authors::AuthorTally::new();
+    for (commit, file_stats) in diff_commits(base, head, languages)? {
+        tally.add_commit(&commit.author, &file_stats);
+    }
+    Ok(tally.finish())
+}
+
+/// Measures the tree of each sampled commit without checking it out.
+fn analyze_history(
+    head: &str,
+    since: Option<i64>,
+    step: history::Step,
+    filter: files::PathFilter,
+    languages: &language::Languages,
+) -> Result<Vec<HistoryRow>, Box<dyn std::error::Error>> {
+    let mut snapshotter = history::Snapshotter::new(filter, generated_detector(languages));
+    let mut rows = Vec::new();
+
+    #[cfg(feature = "git2")]
+    {
+        let repo = open_repository()?;
+        let commits = purecode::libgit::list_first_parent(&repo, head)
+            .map_err(|e| format!("Error listing commits: {e}"))?;
+        let mut blobs = purecode::libgit::RepoBlobSource::new(&repo);
+        for sample in history::sample_commits(commits, since, step) {
+            let tree = purecode::libgit::list_tree(&repo, &sample.commit.sha)
+                .map_err(|e| format!("Error reading tree {}: {e}", sample.commit.sha))?;
+            let snapshot = snapshotter.snapshot(&tree, &mut blobs);
+            rows.extend(history::history_rows(&sample, &snapshot.files));
+        }
+    }
+
+    #[cfg(not(feature = "git2"))]
+    {
+        let commits =
+            diff::list_first_parent(head).map_err(|e| format!("Error listing commits: {e}"))?;
+        let mut blobs = diff::GitBlobSource::default();
+        for sample in history::sample_commits(commits, since, step) {
+            let tree = diff::list_tree(&sample.commit.sha)
+                .map_err(|e| format!("Error reading tree {}: {e}", sample.commit.sha))?;
+            let snapshot = snapshotter.snapshot(&tree, &mut blobs);
+            rows.extend(history::history_rows(&sample, &snapshot.files));
+        }
+    }
+
+    Ok(rows)
+}
+
 fn run() -> Result<ExitCode, Box<dyn std::error::Error>> {
     let cli = Cli::parse();
     let config = config::load_config();
+    let languages = language::Languages::new(config.languages.clone());
 
-    let (stats, mode, active_config) = match cli.command {
+    let ((stats, mut skipped), mode, active_config) = match cli.command {
+        Some(Commands::Authors {
+            paths,
+            blame,
+            rev,
+            base,
+            head,
+            format,
+            ci,
+        }) => {
+            let authors = if blame {
+                let filter = files::PathFilter::new(&include_patterns(&config), &config.exclude);
+                analyze_blame(&rev, &paths, &filter, &languages)?
+            } else {
+                let base = base.unwrap_or(config.base);
+                analyze_commit_authors(&base, &head, &languages)?
+            };
+            let format = resolve_format(format, &config.format);
+            report::print_authors(&authors, format.into(), ci || config.ci);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Commands::Cache { action }) => {
+            match action {
+                CacheAction::Clear => {
+                    let dir = cache::cache_dir().ok_or("Error locating cache directory")?;
+                    let removed = cache::clear(&dir)
+                        .map_err(|e| format!("Error clearing cache {}: {e}", dir.display()))?;
+                    if removed {
+                        println!("Removed {}", dir.display());
+                    } else {
+                        println!("No cache at {}", dir.display());
+                    }
+                }
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Commands::History {
+            since,
+            step,
+            head,
+            format,
+        }) => {
+            let since = since
+                .map(|date| {
+                    history::parse_date(&date)
+                        .ok_or_else(|| format!("Invalid date '{date}', expected YYYY-MM-DD"))
+                })
+                .transpose()?;
+            let filter = files::PathFilter::new(&include_patterns(&config), &config.exclude);
+            let rows = analyze_history(&head, since, step.into(), filter, &languages)?;
+            match format {
+                HistoryFormat::Csv => report::print_history_csv(&rows),
+                HistoryFormat::Json => report::print_history_json(&rows),
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Commands::Log {
+            base,
+            head,
+            format,
+            exclude_reformatted,
+            ci,
+        }) => {
+            let base = base.unwrap_or(config.base);
+            let commits = analyze_log(
+                &base,
+                &head,
+                exclude_reformatted || config.exclude_reformatted,
+                &languages,
+            )?;
+            let format = resolve_format(format, &config.format);
+            report::print_log(&commits, format.into(), ci || config.ci);
+            return Ok(ExitCode::SUCCESS);
+        }
         Some(Commands::Files {
             paths,
             stdin,
+            rev,
+            no_ignore,
+            jobs,
+            max_file_size,
+            no_cache,
             format,
             per_file,
             max_noise_ratio,
             min_pure_lines,
             fail_on_decrease,
+            exclude_generated,
             warn_only,
             ci,
         }) => {
             let final_format = resolve_format(format, &config.format);
-            let include = if config.include.is_empty() {
-                vec!["**/*".to_string()]
-            } else {
-                config.include.clone()
-            };
+            let include = include_patterns(&config);
             let exclude = config.exclude.clone();
 
             let reader: Option<Box<dyn std::io::BufRead>> = if stdin {
@@ -206,8 +806,46 @@ fn run() -> Result<ExitCode, Box<dyn std::error::Error>> {
                 None
             };
 
-            let stats = files::analyze_files(&paths, &include, &exclude, reader)
-                .map_err(|e| format!("Error analyzing files: {e}"))?;
+            let max_file_size = max_file_size
+                .or(config.max_file_size)
+                .unwrap_or(files::DEFAULT_MAX_FILE_SIZE);
+
+            let stats = match rev {
+                Some(rev) => {
+                    let filter = files::PathFilter::new(&include, &exclude);
+                    let snapshot =
+                        analyze_revision(&rev, &paths, filter, max_file_size, &languages)?;
+                    (snapshot.files, snapshot.skipped)
+                }
+                None => {
+                    let options = files::WalkOptions {
+                        no_ignore: no_ignore || config.no_ignore,
+                        jobs: jobs.or(config.jobs).unwrap_or(0),
+                        max_file_size,
+                    };
+                    let cache = if no_cache || config.no_cache {
+                        None
+                    } else {
+                        cache::cache_dir().map(|dir| cache::SnapshotCache::open(&dir, &languages))
+                    };
+                    let snapshot = files::analyze_files(
+                        &paths,
+                        &include,
+                        &exclude,
+                        reader,
+                        options,
+                        cache.as_ref(),
+                        &generated_detector(&languages),
+                    )
+                    .map_err(|e| format!("Error analyzing files: {e}"))?;
+                    if let Some(cache) = cache {
+                        if let Err(e) = cache.save() {
+                            eprintln!("Warning: Could not write cache: {e}");
+                        }
+                    }
+                    (snapshot.files, snapshot.skipped)
+                }
+            };
 
             (
                 stats,
@@ -218,6 +856,8 @@ fn run() -> Result<ExitCode, Box<dyn std::error::Error>> {
                     max_noise_ratio: max_noise_ratio.or(config.max_noise_ratio),
                     min_pure_lines: min_pure_lines.or(config.min_pure_lines),
                     fail_on_decrease: fail_on_decrease || config.fail_on_decrease,
+                    exclude_reformatted: false,
+                    exclude_generated: exclude_generated || config.exclude_generated,
                     warn_only: warn_only || config.warn_only,
                     ci: ci || config.ci,
                 },
@@ -227,26 +867,39 @@ fn run() -> Result<ExitCode, Box<dyn std::error::Error>> {
             base,
             head,
             stdin,
+            staged,
+            worktree,
+            include_untracked,
             format,
             per_file,
             max_noise_ratio,
             min_pure_lines,
             fail_on_decrease,
+            exclude_reformatted,
+            exclude_generated,
             warn_only,
             ci,
         }) => {
             let final_format = resolve_format(format, &config.format);
 
-            let reader: Box<dyn std::io::BufRead> = if stdin {
-                diff::get_stdin_diff()
+            let source = if stdin {
+                DiffSource::Stdin
+            } else if staged || worktree || include_untracked {
+                DiffSource::Local {
+                    changes: if staged {
+                        diff::LocalChanges::Staged
+                    } else {
+                        diff::LocalChanges::Worktree
+                    },
+                    include_untracked,
+                }
             } else {
-                diff::get_git_diff(&base, &head)
-                    .map_err(|e| format!("Error running git diff: {e}"))?
+                DiffSource::Range {
+                    base: &base,
+                    head: &head,
+                }
             };
-
-            let mut file_stats = Vec::new();
-            parser::parse_diff(reader, &mut file_stats)
-                .map_err(|e| format!("Error parsing diff: {e}"))?;
+            let file_stats = analyze_diff(source, &languages)?;
 
             (
                 file_stats,
@@ -257,6 +910,8 @@ fn run() -> Result<ExitCode, Box<dyn std::error::Error>> {
                     max_noise_ratio: max_noise_ratio.or(config.max_noise_ratio),
                     min_pure_lines: min_pure_lines.or(config.min_pure_lines),
                     fail_on_decrease: fail_on_decrease || config.fail_on_decrease,
+                    exclude_reformatted: exclude_reformatted || config.exclude_reformatted,
+                    exclude_generated: exclude_generated || config.exclude_generated,
                     warn_only: warn_only || config.warn_only,
                     ci: ci || config.ci,
                 },
@@ -267,16 +922,15 @@ fn run() -> Result<ExitCode, Box<dyn std::error::Error>> {
             let head = cli.head.unwrap_or("HEAD".to_string());
             let format = resolve_format(cli.format, &config.format);
 
-            let reader: Box<dyn std::io::BufRead> = if cli.stdin {
-                diff::get_stdin_diff()
+            let source = if cli.stdin {
+                DiffSource::Stdin
             } else {
-                diff::get_git_diff(&base, &head)
-                    .map_err(|e| format!("Error running git diff: {e}"))?
+                DiffSource::Range {
+                    base: &base,
+                    head: &head,
+                }
             };
-
-            let mut file_stats = Vec::new();
-            parser::parse_diff(reader, &mut file_stats)
-                .map_err(|e| format!("Error parsing diff: {e}"))?;
+            let file_stats = analyze_diff(source, &languages)?;
 
             (
                 file_stats,
@@ -287,6 +941,8 @@ fn run() -> Result<ExitCode, Box<dyn std::error::Error>> {
                     max_noise_ratio: cli.max_noise_ratio.or(config.max_noise_ratio),
                     min_pure_lines: cli.min_pure_lines.or(config.min_pure_lines),
                     fail_on_decrease: cli.fail_on_decrease || config.fail_on_decrease,
+                    exclude_reformatted: cli.exclude_reformatted || config.exclude_reformatted,
+                    exclude_generated: cli.exclude_generated || config.exclude_generated,
                     warn_only: cli.warn_only || config.warn_only,
                     ci: cli.ci || config.ci,
                 },
@@ -294,8 +950,26 @@ fn run() -> Result<ExitCode, Box<dyn std::error::Error>> {
         }
     };
 
+    // Generated and vendored files get a bucket of their own, or are skipped outright.
+    let (mut generated, mut stats): (Vec<_>, Vec<_>) =
+        stats.into_iter().partition(|file| file.generated);
+    if active_config.exclude_generated {
+        skipped.extend(
+            generated
+                .drain(..)
+                .map(|file| SkippedFile::new(file.path, SkipReason::Generated)),
+        );
+        skipped.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    if active_config.exclude_reformatted {
+        stats::exclude_reformatted(&mut stats);
+    }
+
     report::print_report(
         &stats,
+        &generated,
+        &skipped,
         active_config.format.into(),
         active_config.per_file,
         mode,
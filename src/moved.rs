@@ -0,0 +1,186 @@
+//! Detects pure lines that were removed in one place and added back in another,
+//! in the spirit of `git diff --color-moved`.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// A run of matching lines must contain at least this many alphanumeric
+/// characters to count as moved, the same threshold git uses.
+const MIN_MOVED_ALNUM: usize = 20;
+
+#[derive(Debug, Clone, Copy)]
+struct Line {
+    hash: u64,
+    alnum: usize,
+}
+
+/// Pure lines removed or added together in one file, in diff order.
+#[derive(Debug)]
+struct Block {
+    file: usize,
+    lines: Vec<Line>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Removed,
+    Added,
+}
+
+/// Collects pure lines while a diff is parsed and matches added runs against removed runs.
+#[derive(Debug, Default)]
+pub struct MoveDetector {
+    removed: Vec<Block>,
+    added: Vec<Block>,
+    open: Option<(Side, Block)>,
+}
+
+impl MoveDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a removed pure line of the file at index `file`.
+    pub fn push_removed(&mut self, file: usize, content: &str) {
+        self.push(Side::Removed, file, content);
+    }
+
+    /// Records an added pure line of the file at index `file`.
+    pub fn push_added(&mut self, file: usize, content: &str) {
+        self.push(Side::Added, file, content);
+    }
+
+    /// Ends the current block, e.g. at a context line or hunk boundary.
+    pub fn break_block(&mut self) {
+        if let Some((side, block)) = self.open.take() {
+            match side {
+                Side::Removed => self.removed.push(block),
+                Side::Added => self.added.push(block),
+            }
+        }
+    }
+
+    fn push(&mut self, side: Side, file: usize, content: &str) {
+        if !matches!(&self.open, Some((s, b)) if *s == side && b.file == file) {
+            self.break_block();
+            self.open = Some((
+                side,
+                Block {
+                    file,
+                    lines: Vec::new(),
+                },
+            ));
+        }
+        if let Some((_, block)) = &mut self.open {
+            block.lines.push(normalize(content));
+        }
+    }
+
+    /// Returns the number of moved lines per file, keyed by the file the lines moved to.
+    pub fn finish(mut self) -> HashMap<usize, i64> {
+        self.break_block();
+
+        let mut index: HashMap<u64, Vec<(usize, usize)>> = HashMap::new();
+        for (b, block) in self.removed.iter().enumerate() {
+            for (p, line) in block.lines.iter().enumerate() {
+                index.entry(line.hash).or_default().push((b, p));
+            }
+        }
+        let mut used: Vec<Vec<bool>> = self
+            .removed
+            .iter()
+            .map(|block| vec![false; block.lines.len()])
+            .collect();
+
+        let mut moved: HashMap<usize, i64> = HashMap::new();
+        for block in &self.added {
+            let lines = &block.lines;
+            let mut j = 0;
+            while j < lines.len() {
+                // Runs never start on punctuation-only lines such as `}`,
+                // which match almost anywhere.
+                let candidates = match index.get(&lines[j].hash) {
+                    Some(c) if lines[j].alnum > 0 => c,
+                    _ => {
+                        j += 1;
+                        continue;
+                    }
+                };
+
+                let mut best: Option<(usize, usize, usize)> = None;
+                for &(b, p) in candidates {
+                    let removed = &self.removed[b].lines;
+                    let len = (0..)
+                        .take_while(|&k| {
+                            j + k < lines.len()
+                                && p + k < removed.len()
+                                && !used[b][p + k]
+                                && lines[j + k].hash == removed[p + k].hash
+                        })
+                        .count();
+                    if len > best.map_or(0, |(_, _, l)| l) {
+                        best = Some((b, p, len));
+                    }
+                }
+
+                match best {
+                    Some((b, p, len))
+                        if lines[j..j + len].iter().map(|l| l.alnum).sum::<usize>()
+                            >= MIN_MOVED_ALNUM =>
+                    {
+                        used[b][p..p + len].iter_mut().for_each(|u| *u = true);
+                        *moved.entry(block.file).or_default() += len as i64;
+                        j += len;
+                    }
+                    _ => j += 1,
+                }
+            }
+        }
+        moved
+    }
+}
+
+/// Hashes a line with its whitespace collapsed, so re-indented code still matches.
+fn normalize(content: &str) -> Line {
+    let mut hasher = DefaultHasher::new();
+    let mut alnum = 0;
+    for word in content.split_whitespace() {
+        word.hash(&mut hasher);
+        alnum += word.chars().filter(|c| c.is_alphanumeric()).count();
+    }
+    Line {
+        hash: hasher.finish(),
+        alnum,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moved_block_between_files() {
+        let mut detector = MoveDetector::new();
+        detector.push_removed(0, "fn double_value(input: i32) -> i32 {");
+        detector.push_removed(0, "    input * 2");
+        detector.push_removed(0, "}");
+        detector.break_block();
+        detector.push_added(1, "        fn double_value(input: i32) -> i32 {");
+        detector.push_added(1, "            input * 2");
+        detector.push_added(1, "        }");
+        detector.push_added(1, "let fresh = 1;");
+
+        let moved = detector.finish();
+        assert_eq!(moved.get(&1), Some(&3));
+        assert_eq!(moved.get(&0), None);
+    }
+
+    #[test]
+    fn test_short_runs_are_not_moved() {
+        let mut detector = MoveDetector::new();
+        detector.push_removed(0, "x += 1;");
+        detector.push_added(0, "x += 1;");
+        assert!(detector.finish().is_empty());
+    }
+}
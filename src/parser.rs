@@ -1,178 +1,723 @@
-use crate::classifier::{get_classifier, LineType};
+use crate::classifier::{get_classifier, Classifier, LineType};
+use crate::encoding;
+use crate::generated::GeneratedDetector;
 use crate::language::Language;
-use crate::stats::{FileStats, LangStats};
-use std::path::Path;
+use crate::moved::MoveDetector;
+use crate::reformat;
+use crate::stats::{FileStats, FileStatus, SkipReason, SkippedFile};
+use std::path::PathBuf;
 
-/// Parses a unified diff from the reader and updates statistics.
-pub fn parse_diff<R: std::io::BufRead>(
-    reader: R,
-    stats: &mut Vec<FileStats>,
-) -> Result<(), std::io::Error> {
-    let mut current_file_stats: Option<FileStats> = None;
-    let mut classifier = get_classifier(Language::Other);
-    let mut is_binary_diff = false;
-    let mut context_warning_printed = false;
+/// How many lines from the top of a file are kept to spot generated files
+/// when no blob is available.
+const HEAD_SAMPLE_LINES: usize = 100;
 
-    for line_result in reader.lines() {
-        let line = line_result?;
+/// Supplies file contents by blob id so classifier state can be replayed up to each hunk.
+///
+/// Contents are decoded with [`encoding::decode`]; binary blobs read as `None`.
+pub trait BlobSource {
+    fn read_blob(&mut self, id: &str) -> Option<String>;
+}
 
-        // Detect binary files diff
-        if line.starts_with("Binary files") && line.contains("differ") {
-            // "Binary files a/foo and b/foo differ"
-            // We should skip this file.
-            // If we already started tracking it (unlikely if this is the first line about it), clear it.
-            current_file_stats = None;
-            is_binary_diff = true;
-            continue;
-        }
-
-        if line.starts_with("--- ") {
-            // Save previous
-            if let Some(file_stats) = current_file_stats.take() {
-                if !is_binary_diff
-                    && (file_stats.lang_stats.total_added > 0
-                        || file_stats.lang_stats.total_removed > 0)
-                {
-                    stats.push(file_stats);
-                }
-            }
-            is_binary_diff = false;
+/// Feeds the unchanged lines of a file through a classifier so that a hunk
+/// starting inside a block comment or docstring is classified correctly.
+struct Replay {
+    lines: Vec<String>,
+    /// Index of the next line that has not been fed to the classifier.
+    next: usize,
+}
 
-            let path_part = line.trim_start_matches("--- ").trim();
-            if path_part == "/dev/null" {
-                current_file_stats = None;
-                continue;
+impl Replay {
+    fn new(content: &str) -> Self {
+        Self {
+            lines: content.lines().map(str::to_string).collect(),
+            next: 0,
+        }
+    }
+
+    /// Classifies lines up to, but not including, the 0-based `line`.
+    fn advance_to(&mut self, line: usize, classifier: &mut dyn Classifier) {
+        let end = line.min(self.lines.len());
+        for content in self.lines.iter().take(end).skip(self.next) {
+            classifier.classify(content);
+        }
+        self.next = self.next.max(end);
+    }
+}
+
+/// Lexical state for one side of a file's diff, the pre-image or the post-image.
+struct Side {
+    language: Language,
+    classifier: Box<dyn Classifier>,
+    replay: Option<Replay>,
+}
+
+impl Side {
+    fn new(language: Language) -> Self {
+        Self {
+            classifier: get_classifier(&language),
+            language,
+            replay: None,
+        }
+    }
+
+    /// Classifies a line that exists on this side of the diff.
+    fn classify(&mut self, content: &str) -> LineType {
+        if let Some(replay) = &mut self.replay {
+            replay.next += 1;
+        }
+        self.classifier.classify(content)
+    }
+
+    /// Brings the classifier up to the first line of a hunk covering `start,count` on this side.
+    fn start_hunk(&mut self, start: usize, count: usize) {
+        match &mut self.replay {
+            Some(replay) => {
+                // An empty range starts after `start` rather than at it.
+                let skip = if count == 0 {
+                    start
+                } else {
+                    start.saturating_sub(1)
+                };
+                replay.advance_to(skip, self.classifier.as_mut());
             }
+            None => self.reset(),
+        }
+    }
+
+    fn first_line(&self) -> Option<String> {
+        self.replay.as_ref()?.lines.first().cloned()
+    }
+
+    /// Without the file contents, hunks are disjoint and carrying state
+    /// (like in_comment) across them is dangerous, so start fresh.
+    fn reset(&mut self) {
+        self.classifier = get_classifier(&self.language);
+    }
+}
 
-            let clean_path = if let Some(stripped) = path_part.strip_prefix("a/") {
-                stripped
-            } else {
-                path_part
-            };
+/// Pure lines of one contiguous run of `-`/`+` lines, held back until the run ends
+/// so that reformatted pairs are told apart from real edits and moved code.
+#[derive(Default)]
+struct ChangeGroup {
+    removed: Vec<String>,
+    added: Vec<String>,
+}
 
-            let language = Language::from_path(Path::new(clean_path));
-            classifier = get_classifier(language);
-            current_file_stats = Some(FileStats {
-                path: clean_path.to_string(),
-                language: language.to_string(),
-                lang_stats: LangStats::default(),
-            });
-            continue;
+impl ChangeGroup {
+    /// Counts reformatted pairs and hands the remaining lines to the move detector.
+    fn flush(
+        &mut self,
+        file_stats: Option<&mut FileStats>,
+        file: usize,
+        language: &Language,
+        moves: &mut MoveDetector,
+    ) {
+        if self.removed.is_empty() && self.added.is_empty() {
+            return;
         }
+        let removed = std::mem::take(&mut self.removed);
+        let added = std::mem::take(&mut self.added);
+        let (removed_matched, added_matched) =
+            reformat::match_reformatted(&removed, &added, language);
 
-        if line.starts_with("+++ ") {
-            let path_part = line.trim_start_matches("+++ ").trim();
-            if path_part == "/dev/null" {
-                continue;
+        if let Some(fs) = file_stats {
+            let stat = &mut fs.lang_stats;
+            stat.reformatted_removed += removed_matched.iter().filter(|&&m| m).count() as i64;
+            stat.reformatted_added += added_matched.iter().filter(|&&m| m).count() as i64;
+            stat.reformatted_words_removed += matched_words(&removed, &removed_matched);
+            stat.reformatted_words_added += matched_words(&added, &added_matched);
+        }
+
+        for (line, _) in removed.iter().zip(&removed_matched).filter(|(_, &m)| !m) {
+            moves.push_removed(file, line);
+        }
+        for (line, _) in added.iter().zip(&added_matched).filter(|(_, &m)| !m) {
+            moves.push_added(file, line);
+        }
+        moves.break_block();
+    }
+}
+
+/// Accumulates per-file statistics from a stream of diff events.
+///
+/// The unified diff parser and the in-process git backend both drive it, so
+/// classification, blob replay, reformat and move detection behave the same
+/// whichever of them produced the diff.
+pub struct DiffBuilder<'s, 'b> {
+    stats: &'s mut Vec<FileStats>,
+    blobs: Option<&'b mut dyn BlobSource>,
+    skipped: Vec<SkippedFile>,
+    current: Option<FileStats>,
+    // Removed lines only ever reach the old side and added lines the new side,
+    // so neither can leave the other inside a comment.
+    old_side: Side,
+    new_side: Side,
+    is_binary: bool,
+    pre_image: Option<String>,
+    post_image: Option<String>,
+    replay_loaded: bool,
+    // Set when the post-image is the working tree rather than a blob.
+    worktree: Option<PathBuf>,
+    detector: GeneratedDetector,
+    // The first lines of the post-image seen in the diff, while they are contiguous.
+    head: Vec<String>,
+    head_open: bool,
+    // The current file, once finished, lands at `stats.len()`.
+    moves: MoveDetector,
+    group: ChangeGroup,
+}
+
+impl<'s, 'b> DiffBuilder<'s, 'b> {
+    pub fn new(stats: &'s mut Vec<FileStats>, blobs: Option<&'b mut dyn BlobSource>) -> Self {
+        Self {
+            stats,
+            blobs,
+            skipped: Vec::new(),
+            current: None,
+            old_side: Side::new(Language::Other),
+            new_side: Side::new(Language::Other),
+            is_binary: false,
+            pre_image: None,
+            post_image: None,
+            replay_loaded: false,
+            worktree: None,
+            detector: GeneratedDetector::default(),
+            head: Vec::new(),
+            head_open: false,
+            moves: MoveDetector::new(),
+            group: ChangeGroup::default(),
+        }
+    }
+
+    /// Reads post-images from the files under `root` instead of the blob source,
+    /// for diffs against the working tree.
+    pub fn with_worktree(mut self, root: PathBuf) -> Self {
+        self.worktree = Some(root);
+        self
+    }
+
+    /// Judges generated files and detects languages with `.gitattributes` as well
+    /// as markers, heuristics and extensions.
+    pub fn with_detector(mut self, detector: GeneratedDetector) -> Self {
+        self.detector = detector;
+        self
+    }
+
+    /// Finishes the previous file and starts tracking `new_path`.
+    pub fn start_file(&mut self, old_path: &str, new_path: &str, status: FileStatus) {
+        self.end_file();
+        let language = self.language(new_path);
+        let mut file_stats = FileStats::new(new_path.to_string(), language.to_string(), status);
+        if file_stats.is_relocated() {
+            file_stats.old_path = Some(old_path.to_string());
+        }
+        self.old_side = Side::new(self.language(old_path));
+        self.new_side = Side::new(language);
+        self.current = Some(file_stats);
+        self.head_open = true;
+    }
+
+    /// Finishes the current file, keeping it if it changed any lines or was renamed or copied.
+    pub fn end_file(&mut self) {
+        self.flush_group();
+        self.skip_if_excluded();
+        if let Some(mut file_stats) = self.current.take() {
+            if !self.is_binary
+                && (file_stats.lang_stats.total_added > 0
+                    || file_stats.lang_stats.total_removed > 0
+                    || file_stats.is_relocated())
+            {
+                // Judge by the whole file when a side of it could be read.
+                let (language, lines) = match (&self.new_side.replay, &self.old_side.replay) {
+                    (Some(replay), _) => (&self.new_side.language, &replay.lines),
+                    (None, Some(replay)) => (&self.old_side.language, &replay.lines),
+                    (None, None) => (&self.new_side.language, &self.head),
+                };
+                file_stats.generated = self.detector.is_generated(
+                    &file_stats.path,
+                    language,
+                    lines.iter().map(String::as_str),
+                );
+                self.stats.push(file_stats);
             }
+        }
+        self.head.clear();
+        self.head_open = false;
+        self.is_binary = false;
+        self.replay_loaded = false;
+        self.pre_image = None;
+        self.post_image = None;
+    }
 
-            let clean_path = if let Some(stripped) = path_part.strip_prefix("b/") {
-                stripped
-            } else {
-                path_part
-            };
+    /// Drops the current file if its attributes leave it out of the statistics.
+    /// Checked once the headers, which may rename the file, have been read.
+    fn skip_if_excluded(&mut self) -> bool {
+        let Some(file_stats) = &self.current else {
+            return false;
+        };
+        let Some(reason) = self.detector.attributes().exclusion(&file_stats.path) else {
+            return false;
+        };
+        let path = file_stats.path.clone();
+        self.current = None;
+        self.skip_file(&path, reason);
+        true
+    }
 
-            if let Some(fs) = &mut current_file_stats {
-                if fs.path != clean_path {
-                    let language = Language::from_path(Path::new(clean_path));
-                    classifier = get_classifier(language);
-                    fs.path = clean_path.to_string();
-                    fs.language = language.to_string();
-                }
-            } else {
-                let language = Language::from_path(Path::new(clean_path));
-                classifier = get_classifier(language);
-                current_file_stats = Some(FileStats {
-                    path: clean_path.to_string(),
-                    language: language.to_string(),
-                    lang_stats: LangStats::default(),
-                });
+    fn language(&self, path: &str) -> Language {
+        self.detector.attributes().language(path, None)
+    }
+
+    /// Whether the next line of the post-image is the first line of the file.
+    fn at_first_line(&self) -> bool {
+        self.head_open && self.head.is_empty()
+    }
+
+    /// Lets the first line of a file name the language its path left open,
+    /// on the old or the new side.
+    fn detect_from_first_line(&mut self, first_line: &str, old: bool) {
+        let Some(file_stats) = &mut self.current else {
+            return;
+        };
+        let (side, path) = if old {
+            let path = file_stats.old_path.as_deref().unwrap_or(&file_stats.path);
+            (&mut self.old_side, path)
+        } else {
+            (&mut self.new_side, file_stats.path.as_str())
+        };
+        if side.language != Language::Other {
+            return;
+        }
+        let language = self.detector.attributes().language(path, Some(first_line));
+        if language != Language::Other {
+            side.classifier = get_classifier(&language);
+            if !old {
+                file_stats.language = language.to_string();
+            }
+            side.language = language;
+        }
+    }
+
+    pub fn file_mut(&mut self) -> Option<&mut FileStats> {
+        self.current.as_mut()
+    }
+
+    /// Records the source path of a rename or copy.
+    pub fn set_old_path(&mut self, path: &str) {
+        if let Some(file_stats) = &mut self.current {
+            file_stats.old_path = Some(path.to_string());
+            self.old_side = Side::new(self.language(path));
+        }
+    }
+
+    /// Moves the current file to `path`, re-detecting the post-image language.
+    pub fn set_new_path(&mut self, path: &str) {
+        let language = self.language(path);
+        if let Some(file_stats) = &mut self.current {
+            file_stats.path = path.to_string();
+            file_stats.language = language.to_string();
+            self.new_side = Side::new(language);
+        }
+    }
+
+    /// Names the pre-image and post-image blobs used to replay classifier state.
+    pub fn set_blob_ids(&mut self, pre_image: Option<String>, post_image: Option<String>) {
+        self.pre_image = pre_image;
+        self.post_image = post_image;
+    }
+
+    /// Drops the current file: binary contents cannot be classified.
+    pub fn mark_binary(&mut self) {
+        if let Some(file_stats) = self.current.take() {
+            self.skip_file(&file_stats.path, SkipReason::Binary);
+        }
+        self.is_binary = true;
+    }
+
+    /// Records a file that was left out of the diff statistics.
+    pub fn skip_file(&mut self, path: &str, reason: SkipReason) {
+        self.skipped.push(SkippedFile::new(path, reason));
+    }
+
+    pub fn start_hunk(&mut self, hunk: Option<HunkHeader>) {
+        if self.current.is_none() {
+            return;
+        }
+        self.flush_group();
+        if self.skip_if_excluded() {
+            return;
+        }
+        if !self.head.is_empty() || hunk.is_some_and(|h| h.new_start > 1) {
+            self.head_open = false;
+        }
+
+        if !self.replay_loaded {
+            self.replay_loaded = true;
+            if let Some(source) = self.blobs.as_deref_mut() {
+                self.old_side.replay = load_replay(source, self.pre_image.as_deref());
+                self.new_side.replay = load_replay(source, self.post_image.as_deref());
+            }
+            if let (Some(root), Some(file_stats)) = (&self.worktree, &self.current) {
+                self.new_side.replay = std::fs::read(root.join(&file_stats.path))
+                    .ok()
+                    .and_then(|content| encoding::decode_text(&content))
+                    .map(|content| Replay::new(&content));
+            }
+            if let Some(first_line) = self.old_side.first_line() {
+                self.detect_from_first_line(&first_line, true);
+            }
+            if let Some(first_line) = self.new_side.first_line() {
+                self.detect_from_first_line(&first_line, false);
             }
-            continue;
         }
 
-        // Hunk header
-        if line.starts_with("@@") {
-            // Reset classifier state for new hunk because hunks are disjoint
-            // and carrying state (like in_comment) across hunks is dangerous.
-            // We re-initialize the classifier for the current language.
-            if let Some(fs) = &current_file_stats {
-                let lang = Language::from_path(Path::new(&fs.path));
-                classifier = get_classifier(lang);
+        match hunk {
+            Some(hunk) => {
+                self.old_side.start_hunk(hunk.old_start, hunk.old_count);
+                self.new_side.start_hunk(hunk.new_start, hunk.new_count);
+            }
+            None => {
+                self.old_side.reset();
+                self.new_side.reset();
             }
-            continue;
         }
+    }
 
-        // Ignore metadata
-        if line.starts_with("diff --git")
-            || line.starts_with("index ")
-            || line.starts_with("new file mode")
-            || line.starts_with("deleted file mode")
-        {
-            continue;
+    pub fn added(&mut self, content: &str) {
+        if self.at_first_line() {
+            self.detect_from_first_line(content, false);
+        }
+        let Some(file_stats) = &mut self.current else {
+            return;
+        };
+        let stat = &mut file_stats.lang_stats;
+        stat.total_added += 1;
+        if self.head_open && self.head.len() < HEAD_SAMPLE_LINES {
+            self.head.push(content.to_string());
         }
 
-        if is_binary_diff {
-            continue;
+        match self.new_side.classify(content) {
+            LineType::Pure => {
+                self.group.added.push(content.to_string());
+                stat.pure_added += 1;
+                stat.code_words_added += count_words(content) as i64;
+            }
+            LineType::Comment => stat.comment_lines_added += 1,
+            LineType::Docstring => stat.docstring_lines_added += 1,
+            LineType::Blank => stat.blank_lines_added += 1,
         }
+    }
 
-        let file_stats = match &mut current_file_stats {
-            Some(fs) => fs,
-            None => continue,
+    pub fn removed(&mut self, content: &str) {
+        let Some(file_stats) = &mut self.current else {
+            return;
         };
+        let stat = &mut file_stats.lang_stats;
+        stat.total_removed += 1;
+
+        match self.old_side.classify(content) {
+            LineType::Pure => {
+                self.group.removed.push(content.to_string());
+                stat.pure_removed += 1;
+                stat.code_words_removed += count_words(content) as i64;
+            }
+            LineType::Comment => stat.comment_lines_removed += 1,
+            LineType::Docstring => stat.docstring_lines_removed += 1,
+            LineType::Blank => stat.blank_lines_removed += 1,
+        }
+    }
 
-        if line.starts_with('+') && !line.starts_with("+++") {
-            let content = &line[1..];
-            let stat = &mut file_stats.lang_stats;
-            stat.total_added += 1;
+    /// Context lines are not counted but still carry lexical state on both sides.
+    pub fn context(&mut self, content: &str) {
+        if self.current.is_none() {
+            return;
+        }
+        self.flush_group();
+        if self.at_first_line() {
+            self.detect_from_first_line(content, true);
+            self.detect_from_first_line(content, false);
+        }
+        if self.head_open && self.head.len() < HEAD_SAMPLE_LINES {
+            self.head.push(content.to_string());
+        }
+        self.old_side.classify(content);
+        self.new_side.classify(content);
+    }
 
-            match classifier.classify(content) {
-                LineType::Pure => {
-                    stat.pure_added += 1;
-                    stat.code_words_added += count_words(content) as i64;
+    /// Records a file that has no pre-image, such as an untracked file, as wholly added.
+    pub fn add_file(&mut self, path: &str, content: &str) {
+        self.start_file(path, path, FileStatus::Added);
+        // The whole file is in view, so there is nothing to replay.
+        self.replay_loaded = true;
+        self.start_hunk(None);
+        for line in content.lines() {
+            self.added(line);
+        }
+        self.end_file();
+    }
+
+    /// Finishes the last file, attributes moved lines and returns the skipped files.
+    pub fn finish(mut self) -> Vec<SkippedFile> {
+        self.end_file();
+        for (file, moved) in std::mem::take(&mut self.moves).finish() {
+            if let Some(file_stats) = self.stats.get_mut(file) {
+                file_stats.lang_stats.pure_moved += moved;
+            }
+        }
+        self.skipped
+    }
+
+    fn flush_group(&mut self) {
+        self.group.flush(
+            self.current.as_mut(),
+            self.stats.len(),
+            &self.new_side.language,
+            &mut self.moves,
+        );
+    }
+}
+
+/// Parses a unified diff from the reader and updates statistics, returning
+/// the files it had to skip.
+pub fn parse_diff<R: std::io::BufRead>(
+    reader: R,
+    stats: &mut Vec<FileStats>,
+) -> Result<Vec<SkippedFile>, std::io::Error> {
+    parse_diff_with_blobs(reader, stats, None)
+}
+
+/// Like [`parse_diff`], but fetches the pre-image and post-image blobs named on
+/// `index` lines to replay classifier state between hunks instead of resetting it.
+pub fn parse_diff_with_blobs<R: std::io::BufRead>(
+    reader: R,
+    stats: &mut Vec<FileStats>,
+    blobs: Option<&mut dyn BlobSource>,
+) -> Result<Vec<SkippedFile>, std::io::Error> {
+    let mut builder = DiffBuilder::new(stats, blobs);
+    parse_diff_into(reader, &mut builder)?;
+    Ok(builder.finish())
+}
+
+/// Feeds a unified diff into `builder`, leaving it open for further files.
+pub fn parse_diff_into<R: std::io::BufRead>(
+    reader: R,
+    builder: &mut DiffBuilder,
+) -> Result<(), std::io::Error> {
+    // Set when the file was introduced by a `diff --git` line, whose extended
+    // headers already named the paths that `---`/`+++` would repeat.
+    let mut has_git_header = false;
+    // Lines left in the current hunk; while non-zero every line is hunk content,
+    // even a removed `-- comment` that looks like a `---` header.
+    let mut old_remaining = 0;
+    let mut new_remaining = 0;
+
+    for line_result in reader.lines() {
+        let line = line_result?;
+
+        if old_remaining == 0 && new_remaining == 0 {
+            // Detect binary files diff
+            if line.starts_with("Binary files") && line.contains("differ") {
+                // "Binary files a/foo and b/foo differ"
+                // We should skip this file.
+                builder.mark_binary();
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix("diff --git ") {
+                has_git_header = true;
+                let (old_path, new_path) = split_git_paths(rest);
+                builder.start_file(&old_path, &new_path, FileStatus::Modified);
+                continue;
+            }
+
+            if line.starts_with("--- ") {
+                let path_part = line.trim_start_matches("--- ").trim();
+                if has_git_header {
+                    if let Some(fs) = builder.file_mut() {
+                        if path_part == "/dev/null" {
+                            fs.status = FileStatus::Added;
+                        }
+                    }
+                    continue;
                 }
-                LineType::Comment => stat.comment_lines_added += 1,
-                LineType::Docstring => stat.docstring_lines_added += 1,
-                LineType::Blank => stat.blank_lines_added += 1,
-            }
-        } else if line.starts_with('-') && !line.starts_with("---") {
-            let content = &line[1..];
-            let stat = &mut file_stats.lang_stats;
-            stat.total_removed += 1;
-
-            match classifier.classify(content) {
-                LineType::Pure => {
-                    stat.pure_removed += 1;
-                    stat.code_words_removed += count_words(content) as i64;
+
+                // Plain unified diff: `---` starts the next file.
+                if path_part == "/dev/null" {
+                    builder.end_file();
+                    continue;
                 }
-                LineType::Comment => stat.comment_lines_removed += 1,
-                LineType::Docstring => stat.docstring_lines_removed += 1,
-                LineType::Blank => stat.blank_lines_removed += 1,
+
+                let clean_path = if let Some(stripped) = path_part.strip_prefix("a/") {
+                    stripped
+                } else {
+                    path_part
+                };
+                builder.start_file(clean_path, clean_path, FileStatus::Modified);
+                continue;
+            }
+
+            if line.starts_with("+++ ") {
+                let path_part = line.trim_start_matches("+++ ").trim();
+                if path_part == "/dev/null" {
+                    if let Some(fs) = builder.file_mut() {
+                        fs.status = FileStatus::Deleted;
+                    }
+                    continue;
+                }
+                if has_git_header {
+                    continue;
+                }
+
+                let clean_path = if let Some(stripped) = path_part.strip_prefix("b/") {
+                    stripped
+                } else {
+                    path_part
+                };
+
+                match builder.file_mut() {
+                    Some(fs) if fs.path != clean_path => builder.set_new_path(clean_path),
+                    Some(_) => {}
+                    None => builder.start_file(clean_path, clean_path, FileStatus::Added),
+                }
+                continue;
+            }
+
+            // Hunk header
+            if line.starts_with("@@") {
+                let hunk = parse_hunk_header(&line);
+                if let Some(hunk) = hunk {
+                    old_remaining = hunk.old_count;
+                    new_remaining = hunk.new_count;
+                }
+                builder.start_hunk(hunk);
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix("index ") {
+                // "index 1a2b3c4..5d6e7f8 100644"; all zeros stands for a missing side.
+                let ids = rest
+                    .split_whitespace()
+                    .next()
+                    .and_then(|r| r.split_once(".."));
+                let present = |id: &str| (!id.bytes().all(|b| b == b'0')).then(|| id.to_string());
+                builder.set_blob_ids(
+                    ids.and_then(|(old, _)| present(old)),
+                    ids.and_then(|(_, new)| present(new)),
+                );
+                continue;
             }
-        } else if line.starts_with(' ') {
-            // Context line
-            if !context_warning_printed {
-                eprintln!("Warning: Context line detected. Please use 'git diff --unified=0' for accurate results.");
-                context_warning_printed = true;
+
+            if apply_extended_header(builder, &line) {
+                continue;
             }
         }
-    }
 
-    if let Some(file_stats) = current_file_stats.take() {
-        if !is_binary_diff
-            && (file_stats.lang_stats.total_added > 0 || file_stats.lang_stats.total_removed > 0)
-        {
-            stats.push(file_stats);
+        if let Some(content) = line.strip_prefix('+') {
+            new_remaining = new_remaining.saturating_sub(1);
+            builder.added(content);
+        } else if let Some(content) = line.strip_prefix('-') {
+            old_remaining = old_remaining.saturating_sub(1);
+            builder.removed(content);
+        } else if let Some(content) = line.strip_prefix(' ') {
+            old_remaining = old_remaining.saturating_sub(1);
+            new_remaining = new_remaining.saturating_sub(1);
+            builder.context(content);
         }
     }
 
     Ok(())
 }
 
+/// Splits the `a/old b/new` part of a `diff --git` line. Paths containing
+/// ` b/` are ambiguous here; `rename`/`copy` headers correct them when present.
+fn split_git_paths(rest: &str) -> (String, String) {
+    let rest = rest.trim();
+    match rest.strip_prefix("a/").and_then(|r| r.split_once(" b/")) {
+        Some((old, new)) => (old.to_string(), new.to_string()),
+        None => (rest.to_string(), rest.to_string()),
+    }
+}
+
+/// Applies a git extended header line such as `rename from` or `new file mode`.
+/// Returns false if the line is not one.
+fn apply_extended_header(builder: &mut DiffBuilder, line: &str) -> bool {
+    let Some(fs) = builder.file_mut() else {
+        return false;
+    };
+    if line.starts_with("new file mode") {
+        fs.status = FileStatus::Added;
+    } else if line.starts_with("deleted file mode") {
+        fs.status = FileStatus::Deleted;
+    } else if let Some(percent) = line.strip_prefix("similarity index ") {
+        fs.similarity = percent.trim().trim_end_matches('%').parse().ok();
+    } else if line.starts_with("old mode")
+        || line.starts_with("new mode")
+        || line.starts_with("dissimilarity index ")
+    {
+        // Mode changes and rewrite scores carry no lines to classify.
+    } else if let Some(from) = line.strip_prefix("rename from ") {
+        fs.status = FileStatus::Renamed;
+        builder.set_old_path(from);
+    } else if let Some(from) = line.strip_prefix("copy from ") {
+        fs.status = FileStatus::Copied;
+        builder.set_old_path(from);
+    } else if let Some(to) = line
+        .strip_prefix("rename to ")
+        .or_else(|| line.strip_prefix("copy to "))
+    {
+        builder.set_new_path(to);
+    } else {
+        return false;
+    }
+    true
+}
+
+fn load_replay(source: &mut dyn BlobSource, id: Option<&str>) -> Option<Replay> {
+    source.read_blob(id?).map(|content| Replay::new(&content))
+}
+
+/// Line ranges from a `@@ -old_start,old_count +new_start,new_count @@` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HunkHeader {
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+}
+
+fn parse_hunk_header(line: &str) -> Option<HunkHeader> {
+    let mut parts = line.strip_prefix("@@ ")?.split_whitespace();
+    let (old_start, old_count) = parse_range(parts.next()?.strip_prefix('-')?)?;
+    let (new_start, new_count) = parse_range(parts.next()?.strip_prefix('+')?)?;
+    Some(HunkHeader {
+        old_start,
+        old_count,
+        new_start,
+        new_count,
+    })
+}
+
+/// Parses `start[,count]`; the count defaults to one.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
 fn count_words(line: &str) -> usize {
     line.split_whitespace().count()
 }
 
+fn matched_words(lines: &[String], matched: &[bool]) -> i64 {
+    lines
+        .iter()
+        .zip(matched)
+        .filter(|(_, &m)| m)
+        .map(|(line, _)| count_words(line) as i64)
+        .sum()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -206,4 +751,233 @@ index 123..456 100644
         assert_eq!(lang_stats.pure_removed, 1);
         assert_eq!(lang_stats.pure_added, 2);
     }
+
+    #[test]
+    fn test_binary_files_are_reported_as_skipped() {
+        let diff_input = "\
+diff --git a/logo.png b/logo.png
+index 1111111..2222222 100644
+Binary files a/logo.png and b/logo.png differ
+diff --git a/main.rs b/main.rs
+--- a/main.rs
++++ b/main.rs
+@@ -1 +1 @@
+-fn old() {}
++fn new() {}
+";
+        let mut stats = Vec::new();
+        let skipped = parse_diff(Cursor::new(diff_input), &mut stats).unwrap();
+
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].path, "main.rs");
+        assert_eq!(skipped, [SkippedFile::new("logo.png", SkipReason::Binary)]);
+    }
+
+    #[test]
+    fn test_context_lines_seed_classifier() {
+        let diff_input = "\
+--- a/lib.rs
++++ b/lib.rs
+@@ -1,2 +1,3 @@
+ /* Opened in context
++added inside the comment
+ */
+";
+        let mut stats = Vec::new();
+        parse_diff(Cursor::new(diff_input), &mut stats).unwrap();
+
+        let lang_stats = &stats[0].lang_stats;
+        assert_eq!(lang_stats.total_added, 1);
+        assert_eq!(lang_stats.comment_lines_added, 1);
+        assert_eq!(lang_stats.pure_added, 0);
+    }
+
+    struct FakeBlobs(&'static str);
+
+    impl BlobSource for FakeBlobs {
+        fn read_blob(&mut self, id: &str) -> Option<String> {
+            (id == "def456").then(|| self.0.to_string())
+        }
+    }
+
+    #[test]
+    fn test_old_and_new_sides_are_independent() {
+        let diff_input = "\
+--- a/lib.rs
++++ b/lib.rs
+@@ -1 +1 @@
+-/* removed opener
++let x = 1;
+";
+        let mut stats = Vec::new();
+        parse_diff(Cursor::new(diff_input), &mut stats).unwrap();
+
+        let lang_stats = &stats[0].lang_stats;
+        assert_eq!(lang_stats.comment_lines_removed, 1);
+        assert_eq!(lang_stats.pure_added, 1);
+    }
+
+    #[test]
+    fn test_renames_and_copies() {
+        let diff_input = "\
+diff --git a/old.py b/new.py
+similarity index 100%
+rename from old.py
+rename to new.py
+diff --git a/src/a.rs b/src/b.rs
+similarity index 90%
+copy from src/a.rs
+copy to src/b.rs
+index abc123..def456 100644
+--- a/src/a.rs
++++ b/src/b.rs
+@@ -1 +1 @@
+-fn a() {}
++fn b() {}
+diff --git a/run.sh b/run.sh
+old mode 100644
+new mode 100755
+diff --git a/gone.rs b/gone.rs
+deleted file mode 100644
+index abc123..0000000
+--- a/gone.rs
++++ /dev/null
+@@ -1 +0,0 @@
+--- not a header: removed line starting with two dashes
+";
+        let mut stats = Vec::new();
+        parse_diff(Cursor::new(diff_input), &mut stats).unwrap();
+
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0].path, "new.py");
+        assert_eq!(stats[0].old_path.as_deref(), Some("old.py"));
+        assert_eq!(stats[0].status, FileStatus::Renamed);
+        assert_eq!(stats[0].similarity, Some(100));
+        assert_eq!(stats[0].lang_stats.total_added, 0);
+
+        assert_eq!(stats[1].path, "src/b.rs");
+        assert_eq!(stats[1].status, FileStatus::Copied);
+        assert_eq!(stats[1].similarity, Some(90));
+        assert_eq!(stats[1].lang_stats.pure_added, 1);
+
+        assert_eq!(stats[2].path, "gone.rs");
+        assert_eq!(stats[2].status, FileStatus::Deleted);
+        assert_eq!(stats[2].lang_stats.total_removed, 1);
+    }
+
+    #[test]
+    fn test_reformatted_lines_are_not_moved() {
+        let diff_input = "\
+--- a/app.py
++++ b/app.py
+@@ -1,2 +1,2 @@
+-result = compute_totals(orders,discount)
+-print('done')
++result = compute_totals(orders, discount)
++print(\"done\")
+";
+        let mut stats = Vec::new();
+        parse_diff(Cursor::new(diff_input), &mut stats).unwrap();
+
+        let lang_stats = &stats[0].lang_stats;
+        assert_eq!(lang_stats.reformatted_added, 2);
+        assert_eq!(lang_stats.reformatted_removed, 2);
+        assert_eq!(lang_stats.reformatted_words_added, 5);
+        assert_eq!(lang_stats.reformatted_words_removed, 4);
+        assert_eq!(lang_stats.pure_moved, 0);
+
+        crate::stats::exclude_reformatted(&mut stats);
+        let lang_stats = &stats[0].lang_stats;
+        assert_eq!(lang_stats.pure_added, 0);
+        assert_eq!(lang_stats.code_words_added, 0);
+        assert_eq!(lang_stats.code_words_removed, 0);
+    }
+
+    #[test]
+    fn test_blob_replay_between_hunks() {
+        let post_image = "\
+fn a() {}
+/*
+still a comment
+added one
+added two
+*/
+fn b() {}
+";
+        let diff_input = "\
+diff --git a/lib.rs b/lib.rs
+index abc123..def456 100644
+--- a/lib.rs
++++ b/lib.rs
+@@ -3,0 +4,2 @@
++added one
++added two
+@@ -5,0 +8 @@
++fn c() {}
+";
+        let mut stats = Vec::new();
+        let mut blobs = FakeBlobs(post_image);
+        parse_diff_with_blobs(Cursor::new(diff_input), &mut stats, Some(&mut blobs)).unwrap();
+
+        let lang_stats = &stats[0].lang_stats;
+        assert_eq!(lang_stats.comment_lines_added, 2);
+        assert_eq!(lang_stats.pure_added, 1);
+    }
+
+    #[test]
+    fn test_generated_files_are_flagged_from_their_head() {
+        let diff_input = "\
+--- /dev/null
++++ b/api/service.go
+@@ -0,0 +1,2 @@
++// Code generated by protoc-gen-go. DO NOT EDIT.
++package api
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -40,1 +40,1 @@
+-// DO NOT EDIT
++fn main() {}
+";
+        let mut stats = Vec::new();
+        parse_diff(Cursor::new(diff_input), &mut stats).unwrap();
+
+        assert!(stats[0].generated);
+        // A marker in a hunk further down the file says nothing about it.
+        assert!(!stats[1].generated);
+    }
+
+    #[test]
+    fn test_scripts_are_detected_by_their_first_line() {
+        let diff_input = "\
+--- /dev/null
++++ b/bin/deploy
+@@ -0,0 +1,3 @@
++#!/usr/bin/env bash
++# Ships the current build.
++rsync -a dist/ host:/srv/
+";
+        let mut stats = Vec::new();
+        parse_diff(Cursor::new(diff_input), &mut stats).unwrap();
+
+        assert_eq!(stats[0].language, "Shell");
+        let lang_stats = &stats[0].lang_stats;
+        assert_eq!(lang_stats.comment_lines_added, 2);
+        assert_eq!(lang_stats.pure_added, 1);
+    }
+
+    #[test]
+    fn test_add_file_counts_every_line() {
+        let mut stats = Vec::new();
+        let mut builder = DiffBuilder::new(&mut stats, None);
+        builder.add_file("tool.py", "# helper\n\ndef run():\n    return 1\n");
+        builder.finish();
+
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].status, FileStatus::Added);
+        let s = &stats[0].lang_stats;
+        assert_eq!(s.total_added, 4);
+        assert_eq!(s.comment_lines_added, 1);
+        assert_eq!(s.blank_lines_added, 1);
+        assert_eq!(s.pure_added, 2);
+    }
 }
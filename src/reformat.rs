@@ -0,0 +1,428 @@
+//! Detects removed/added line pairs that differ only in formatting, as produced
+//! by rustfmt, black or prettier.
+
+use crate::language::Language;
+
+/// Whether `'` and `"` delimit the same kind of string, so formatters may swap them.
+pub fn interchangeable_quotes(lang: &Language) -> bool {
+    matches!(
+        lang,
+        Language::Python | Language::JavaScript | Language::TypeScript | Language::Vue
+    )
+}
+
+/// Whether leading indentation is part of the syntax, so re-indenting a line changes it.
+pub fn significant_indentation(lang: &Language) -> bool {
+    matches!(lang, Language::Python | Language::Yaml)
+}
+
+/// Groups larger than this (removed times added lines) are paired by a greedy
+/// in-order walk instead of a longest common subsequence.
+const MAX_LCS_CELLS: usize = 1 << 22;
+
+/// Pairs removed and added lines of one change group that are identical after
+/// normalization, in order, returning which removed and which added lines were paired.
+///
+/// If the whole group normalizes to the same token stream, every line is paired,
+/// which covers formatters joining or splitting lines.
+pub fn match_reformatted(
+    removed: &[String],
+    added: &[String],
+    lang: &Language,
+) -> (Vec<bool>, Vec<bool>) {
+    let quotes = interchangeable_quotes(lang);
+    let indentation = significant_indentation(lang);
+
+    if !removed.is_empty()
+        && !added.is_empty()
+        && normalize_group(removed, quotes, indentation)
+            == normalize_group(added, quotes, indentation)
+    {
+        return (vec![true; removed.len()], vec![true; added.len()]);
+    }
+
+    let normalize_line = |line: &String| {
+        let indent = if indentation {
+            leading_whitespace(line)
+        } else {
+            ""
+        };
+        let mut tokens = Vec::new();
+        tokenize(line, quotes, &mut tokens);
+        format!("{indent}{}", render(&tokens))
+    };
+    let removed_norm: Vec<String> = removed.iter().map(normalize_line).collect();
+    let added_norm: Vec<String> = added.iter().map(normalize_line).collect();
+
+    let mut removed_matched = vec![false; removed.len()];
+    let mut added_matched = vec![false; added.len()];
+    for (r, a) in pair_in_order(&removed_norm, &added_norm) {
+        removed_matched[r] = true;
+        added_matched[a] = true;
+    }
+    (removed_matched, added_matched)
+}
+
+/// Pairs equal lines without crossing, so reordered lines are not taken for a reformat.
+fn pair_in_order(removed: &[String], added: &[String]) -> Vec<(usize, usize)> {
+    let (n, m) = (removed.len(), added.len());
+    let mut pairs = Vec::new();
+    if n.saturating_mul(m) > MAX_LCS_CELLS {
+        let mut next = 0;
+        for (a, line) in added.iter().enumerate() {
+            if let Some(offset) = removed[next..].iter().position(|r| r == line) {
+                pairs.push((next + offset, a));
+                next += offset + 1;
+            }
+        }
+        return pairs;
+    }
+
+    // lcs[r][a] is the longest common subsequence of removed[r..] and added[a..].
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for r in (0..n).rev() {
+        for a in (0..m).rev() {
+            lcs[r][a] = if removed[r] == added[a] {
+                lcs[r + 1][a + 1] + 1
+            } else {
+                lcs[r + 1][a].max(lcs[r][a + 1])
+            };
+        }
+    }
+    let (mut r, mut a) = (0, 0);
+    while r < n && a < m {
+        if removed[r] == added[a] {
+            pairs.push((r, a));
+            r += 1;
+            a += 1;
+        } else if lcs[r + 1][a] >= lcs[r][a + 1] {
+            r += 1;
+        } else {
+            a += 1;
+        }
+    }
+    pairs
+}
+
+/// A token of a normalized line.
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    /// A run of word or operator characters.
+    Run(String),
+    /// A bracket, separator or stray quote, which never merges with a neighbour.
+    Punct(char),
+    /// A string literal, kept verbatim apart from its delimiters.
+    Literal(String),
+    /// The start of a line, with its indentation where that starts a statement.
+    Line(Option<String>),
+}
+
+/// Normalizes a run of lines as one token stream. Where indentation is
+/// significant, each line that starts a statement outside of brackets keeps it.
+fn normalize_group(lines: &[String], quotes: bool, indentation: bool) -> String {
+    let mut tokens = Vec::new();
+    let mut depth = 0i64;
+    for line in lines {
+        let indent = (indentation && depth <= 0).then(|| leading_whitespace(line).to_string());
+        tokens.push(Token::Line(indent));
+        let start = tokens.len();
+        tokenize(line, quotes, &mut tokens);
+        depth += tokens[start..]
+            .iter()
+            .map(|token| match token {
+                Token::Punct('(' | '[' | '{') => 1,
+                Token::Punct(')' | ']' | '}') => -1,
+                _ => 0,
+            })
+            .sum::<i64>();
+    }
+    strip_trailing_commas(&mut tokens);
+    render(&tokens)
+}
+
+/// Splits a line into tokens, dropping whitespace. String literals are kept
+/// whole, so the whitespace inside them still counts; where allowed, their
+/// quotes are unified.
+fn tokenize(line: &str, quotes: bool, tokens: &mut Vec<Token>) {
+    let chars: Vec<char> = line.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' || (c == '\'' && (quotes || starts_char_literal(&chars[i..]))) {
+            let len = literal_len(&chars[i..]);
+            let literal = &chars[i..i + len];
+            tokens.push(Token::Literal(if quotes {
+                unify_quotes(literal)
+            } else {
+                literal.iter().collect()
+            }));
+            i += len;
+        } else if let Some(class) = char_class(c) {
+            let len = chars[i..]
+                .iter()
+                .take_while(|&&c| char_class(c) == Some(class))
+                .count();
+            tokens.push(Token::Run(chars[i..i + len].iter().collect()));
+            i += len;
+        } else {
+            tokens.push(Token::Punct(c));
+            i += 1;
+        }
+    }
+}
+
+/// The length of the string literal at the start of `chars`, up to and
+/// including the closing quote, or to the end of the line if it has none.
+fn literal_len(chars: &[char]) -> usize {
+    let quote = chars[0];
+    let mut i = 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            c if c == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+/// Whether a char literal such as `'a'` or `'\n'` starts `chars`, telling it
+/// apart from a lifetime or a stray apostrophe.
+fn starts_char_literal(chars: &[char]) -> bool {
+    match chars.get(1) {
+        Some('\\') => chars.iter().skip(2).take(10).any(|&c| c == '\''),
+        Some('\'') | None => false,
+        Some(_) => chars.get(2) == Some(&'\''),
+    }
+}
+
+/// Rewrites a literal with double quotes, escaping only the double quotes of
+/// its value, so `'it"s'` and `"it\"s"` agree while `"it"s"` stays apart.
+fn unify_quotes(literal: &[char]) -> String {
+    let quote = literal[0];
+    let closed = literal.len() > 1 && literal[literal.len() - 1] == quote;
+    let body = &literal[1..literal.len() - usize::from(closed)];
+    let mut out = String::from('"');
+    let mut i = 0;
+    while i < body.len() {
+        match (body[i], body.get(i + 1)) {
+            ('\\', Some(&next)) if next == '"' || next == '\'' => {
+                if next == '"' {
+                    out.push('\\');
+                }
+                out.push(next);
+                i += 2;
+            }
+            ('\\', Some(&next)) => {
+                out.push('\\');
+                out.push(next);
+                i += 2;
+            }
+            ('"', _) => {
+                out.push_str("\\\"");
+                i += 1;
+            }
+            (c, _) => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    if closed {
+        out.push('"');
+    }
+    out
+}
+
+/// Joins tokens back into a string, with a space only between two word or two
+/// operator runs, so that `return x` and `returnx`, or `a - -b` and `a --b`,
+/// stay apart.
+fn render(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    let mut last_class = None;
+    for token in tokens {
+        match token {
+            Token::Run(run) => {
+                let class = run.chars().next().and_then(char_class);
+                if class.is_some() && class == last_class {
+                    out.push(' ');
+                }
+                out.push_str(run);
+                last_class = class;
+                continue;
+            }
+            Token::Punct(c) => out.push(*c),
+            Token::Literal(literal) => out.push_str(literal),
+            Token::Line(Some(indent)) => {
+                out.push('\n');
+                out.push_str(indent);
+            }
+            Token::Line(None) => continue,
+        }
+        last_class = None;
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Word,
+    Operator,
+}
+
+/// Brackets, separators and quotes never merge with a neighbour and have no class.
+fn char_class(c: char) -> Option<CharClass> {
+    if c.is_alphanumeric() || c == '_' {
+        Some(CharClass::Word)
+    } else if matches!(
+        c,
+        '(' | ')' | '[' | ']' | '{' | '}' | ',' | ';' | '"' | '\'' | '`'
+    ) || c.is_whitespace()
+    {
+        None
+    } else {
+        Some(CharClass::Operator)
+    }
+}
+
+fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Removes the trailing comma formatters add or drop when they split a
+/// bracket over several lines: one that ends a line, right before the closing
+/// bracket. A bracket holding a single element keeps it, since `(1,)` is a
+/// tuple and `(1)` is not.
+fn strip_trailing_commas(tokens: &mut Vec<Token>) {
+    let mut commas: Vec<usize> = Vec::new();
+    let mut trailing = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punct('(' | '[' | '{') => commas.push(0),
+            Token::Punct(')' | ']' | '}') => {
+                commas.pop();
+            }
+            Token::Punct(',') => {
+                let Some(count) = commas.last_mut() else {
+                    continue;
+                };
+                *count += 1;
+                let mut rest = tokens[i + 1..].iter();
+                let ends_line = matches!(rest.next(), Some(Token::Line(_)));
+                let closes = matches!(
+                    rest.find(|t| !matches!(t, Token::Line(_))),
+                    Some(Token::Punct(')' | ']' | '}'))
+                );
+                if ends_line && closes && *count > 1 {
+                    trailing.push(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    for i in trailing.into_iter().rev() {
+        tokens.remove(i);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(src: &[&str]) -> Vec<String> {
+        src.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_reindented_lines_pair_up() {
+        let removed = lines(&["if x {", "  run(a,b);", "}"]);
+        let added = lines(&["if x {", "    run(a, b);", "    other();", "}"]);
+        let (r, a) = match_reformatted(&removed, &added, &Language::Rust);
+        assert_eq!(r, vec![true, true, true]);
+        assert_eq!(a, vec![true, true, false, true]);
+    }
+
+    #[test]
+    fn test_split_call_is_reformat() {
+        let removed = lines(&["call('a', 'b')"]);
+        let added = lines(&["call(", "    \"a\",", "    \"b\",", ")"]);
+        let (r, a) = match_reformatted(&removed, &added, &Language::Python);
+        assert_eq!(r, vec![true]);
+        assert_eq!(a, vec![true; 4]);
+
+        // In C a char literal is not a string.
+        let (r, _) = match_reformatted(&removed, &added, &Language::C);
+        assert_eq!(r, vec![false]);
+    }
+
+    #[test]
+    fn test_whitespace_still_separates_tokens() {
+        let pair = |removed: &str, added: &str, lang| {
+            let (r, _) = match_reformatted(&lines(&[removed]), &lines(&[added]), lang);
+            r[0]
+        };
+        assert!(pair("return  x;", "return x;", &Language::Rust));
+        assert!(pair("let y=a-b;", "let y = a - b;", &Language::Rust));
+        assert!(!pair("return x;", "returnx;", &Language::Rust));
+        assert!(!pair("y = a - -b;", "y = a --b;", &Language::C));
+    }
+
+    #[test]
+    fn test_reindented_python_is_not_a_reformat() {
+        let removed = lines(&["if ready:", "    start()", "stop()"]);
+        let added = lines(&["if ready:", "    start()", "    stop()"]);
+        let (r, a) = match_reformatted(&removed, &added, &Language::Python);
+        assert_eq!(r, vec![true, true, false]);
+        assert_eq!(a, vec![true, true, false]);
+
+        // The same change is only layout in a language with braces.
+        let (r, _) = match_reformatted(&removed, &added, &Language::Rust);
+        assert_eq!(r, vec![true; 3]);
+    }
+
+    #[test]
+    fn test_reordered_lines_are_not_a_reformat() {
+        let removed = lines(&["a = 1;", "b = 2;", "c = 3;"]);
+        let added = lines(&["c = 3;", "b = 2;", "a = 1;"]);
+        let (r, a) = match_reformatted(&removed, &added, &Language::C);
+        // Only one line can stay in place; the others moved.
+        assert_eq!(r.iter().filter(|&&m| m).count(), 1);
+        assert_eq!(a.iter().filter(|&&m| m).count(), 1);
+    }
+
+    #[test]
+    fn test_trailing_comma_only_dropped_when_splitting() {
+        let pair = |removed: &[&str], added: &[&str]| {
+            let (r, _) = match_reformatted(&lines(removed), &lines(added), &Language::Python);
+            r[0]
+        };
+        assert!(!pair(&["x = (1,)"], &["x = (1)"]));
+        assert!(!pair(&["x = (", "    1,", ")"], &["x = (1)"]));
+        assert!(!pair(&["f(a, b,)"], &["f(a, b)"]));
+        assert!(pair(&["f(a, b)"], &["f(", "    a,", "    b,", ")"]));
+    }
+
+    #[test]
+    fn test_string_literals_compare_verbatim() {
+        let pair = |removed: &str, added: &str, lang| {
+            let (r, _) = match_reformatted(&lines(&[removed]), &lines(&[added]), lang);
+            r[0]
+        };
+        assert!(!pair(
+            "print(\"a  b\")",
+            "print(\"a b\")",
+            &Language::Python
+        ));
+        assert!(!pair("s = 'it\"s'", "s = \"it\"s\"", &Language::Python));
+        assert!(pair("s = 'it\"s'", "s = \"it\\\"s\"", &Language::Python));
+        assert!(pair("s = 'don\\'t'", "s = \"don't\"", &Language::Python));
+        assert!(!pair("c = ' ';", "c = '  ';", &Language::C));
+        assert!(pair(
+            "fn f<'a>(x: &'a str) {}",
+            "fn f<'a>(x: &'a  str) {}",
+            &Language::Rust
+        ));
+    }
+}
@@ -1,8 +1,10 @@
 use crate::stats::{
-    aggregate_stats, calculate_complexity, estimate_tokens, AnalysisResult, FileStats, LangStats,
+    aggregate_stats, calculate_complexity, estimate_tokens, AnalysisResult, AuthorStats,
+    CommitStats, FileStats, GeneratedStats, HistoryRow, LangStats, Relocation, SkipReason,
+    SkippedFile,
 };
 use colored::Colorize;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OutputFormat {
@@ -13,27 +15,36 @@ pub enum OutputFormat {
 
 pub fn print_report(
     stats: &[FileStats],
+    generated: &[FileStats],
+    skipped: &[SkippedFile],
     format: OutputFormat,
     per_file: bool,
     mode: &str,
     ci: bool,
 ) {
     let overall = aggregate_stats(stats);
-    let lang_map = aggregate_by_language(stats);
-
     let complexity = calculate_complexity(&overall);
-    let token_estimate = estimate_tokens(overall.code_words_added);
+    let result = AnalysisResult {
+        summary: overall,
+        language_stats: aggregate_by_language(stats),
+        file_stats: if per_file {
+            Some([stats, generated].concat())
+        } else {
+            None
+        },
+        relocations: collect_relocations(stats),
+        skipped: skipped.to_vec(),
+        generated: (!generated.is_empty()).then(|| GeneratedStats {
+            files: generated.len(),
+            lang_stats: aggregate_stats(generated),
+        }),
+        complexity_score: complexity,
+        token_estimate: estimate_tokens(overall.code_words_added),
+        mode: mode.to_string(),
+    };
 
     match format {
         OutputFormat::Json => {
-            let result = AnalysisResult {
-                summary: overall,
-                language_stats: lang_map,
-                file_stats: if per_file { Some(stats.to_vec()) } else { None },
-                complexity_score: complexity,
-                token_estimate,
-                mode: mode.to_string(),
-            };
             let json = serde_json::to_string_pretty(&result)
                 .expect("Failed to serialize analysis result to JSON");
             println!("{json}");
@@ -42,23 +53,9 @@ pub fn print_report(
             let use_color = !ci && format == OutputFormat::Human;
 
             if use_color {
-                print_human_report(
-                    stats,
-                    &overall,
-                    &lang_map,
-                    per_file,
-                    complexity,
-                    token_estimate,
-                );
+                print_human_report(stats, generated, &result, per_file);
             } else {
-                print_plain_report(
-                    stats,
-                    &overall,
-                    &lang_map,
-                    per_file,
-                    complexity,
-                    token_estimate,
-                );
+                print_plain_report(stats, generated, &result, per_file);
             }
         }
     }
@@ -74,37 +71,53 @@ pub fn print_report(
         };
 
         println!(
-            "PURECODE_SUMMARY noise_ratio={noise_ratio:.2} pure_added={} pure_removed={} files_changed={} complexity={complexity:.2}",
+            "PURECODE_SUMMARY noise_ratio={noise_ratio:.2} pure_added={} pure_removed={} pure_moved={} reformatted={} files_changed={} files_relocated={} files_skipped={} files_generated={} complexity={complexity:.2}",
             overall.pure_added,
             overall.pure_removed,
+            overall.pure_moved,
+            overall.reformatted_added,
             stats.len(),
+            result.relocations.len(),
+            skipped.len(),
+            generated.len(),
         );
     }
 }
 
 fn print_human_report(
     files: &[FileStats],
-    overall: &LangStats,
-    lang_map: &HashMap<String, LangStats>,
+    generated: &[FileStats],
+    result: &AnalysisResult,
     per_file: bool,
-    complexity: f64,
-    tokens: u64,
 ) {
+    let overall = &result.summary;
+    let skipped = &result.skipped;
+    let complexity = result.complexity_score;
     println!("{}", "PureCode Analysis Report".bold().underline());
     println!("Total Files: {}", files.len());
+    if !skipped.is_empty() {
+        println!("Skipped Files: {}", summarize_skipped(skipped).yellow());
+    }
+    if let Some(bucket) = &result.generated {
+        println!(
+            "Generated Files: {} (Net Pure Lines: {}, not counted)",
+            bucket.files,
+            bucket.lang_stats.net_pure()
+        );
+    }
     println!("Net Pure Lines: {}", overall.net_pure().to_string().cyan());
+    println!("Moved Pure Lines: {}", overall.pure_moved);
+    println!("Reformatted Lines: {}", overall.reformatted_added);
     println!(
         "Review Complexity: {:.1} ({})",
         complexity,
         complexity_bucket(complexity)
     );
-    println!("Estimated Tokens (Added): {tokens}");
+    println!("Estimated Tokens (Added): {}", result.token_estimate);
 
     println!("\n{}", "Language Breakdown:".bold());
-    let mut sorted_langs: Vec<_> = lang_map.iter().collect();
-    sorted_langs.sort_by_key(|(k, _)| *k);
 
-    for (lang, stat) in sorted_langs {
+    for (lang, stat) in &result.language_stats {
         println!(
             "  {:<12} | Pure: {:>4} | Added: {:>4} | Removed: {:>4} | Noise: {:>4}",
             lang.blue(),
@@ -115,43 +128,80 @@ fn print_human_report(
         );
     }
 
+    if !result.relocations.is_empty() {
+        println!("\n{}", "Renames and Copies:".bold());
+        for relocation in &result.relocations {
+            println!("  {}", format_relocation(relocation).magenta());
+        }
+    }
+
     if per_file {
         println!("\n{}", "File Details:".bold());
         for file in files {
             println!(
-                "  {:<30} [{}] | Pure: {:>3}",
+                "  {:<30} [{}] | Pure: {:>3} | {}",
                 file.path,
                 file.language.yellow(),
-                file.lang_stats.net_pure()
+                file.lang_stats.net_pure(),
+                file.status
             );
         }
+        if !generated.is_empty() {
+            println!("\n{}", "Generated Files:".bold());
+            for file in generated {
+                println!(
+                    "  {:<30} [{}] | Pure: {:>3} | {}",
+                    file.path,
+                    file.language.yellow(),
+                    file.lang_stats.net_pure(),
+                    file.status
+                );
+            }
+        }
+        if !skipped.is_empty() {
+            println!("\n{}", "Skipped Files:".bold());
+            for file in skipped {
+                println!("  {:<30} | {}", file.path, file.reason.to_string().yellow());
+            }
+        }
     }
     println!();
 }
 
 fn print_plain_report(
     files: &[FileStats],
-    overall: &LangStats,
-    lang_map: &HashMap<String, LangStats>,
+    generated: &[FileStats],
+    result: &AnalysisResult,
     per_file: bool,
-    complexity: f64,
-    tokens: u64,
 ) {
+    let overall = &result.summary;
+    let skipped = &result.skipped;
+    let complexity = result.complexity_score;
     println!("PureCode Analysis Report");
     println!("Total Files: {}", files.len());
+    if !skipped.is_empty() {
+        println!("Skipped Files: {}", summarize_skipped(skipped));
+    }
+    if let Some(bucket) = &result.generated {
+        println!(
+            "Generated Files: {} (Net Pure Lines: {}, not counted)",
+            bucket.files,
+            bucket.lang_stats.net_pure()
+        );
+    }
     println!("Net Pure Lines: {}", overall.net_pure());
+    println!("Moved Pure Lines: {}", overall.pure_moved);
+    println!("Reformatted Lines: {}", overall.reformatted_added);
     println!(
         "Review Complexity: {:.1} ({})",
         complexity,
         complexity_bucket(complexity)
     );
-    println!("Estimated Tokens (Added): {tokens}");
+    println!("Estimated Tokens (Added): {}", result.token_estimate);
 
     println!("\nLanguage Breakdown:");
-    let mut sorted_langs: Vec<_> = lang_map.iter().collect();
-    sorted_langs.sort_by_key(|(k, _)| *k);
 
-    for (lang, stat) in sorted_langs {
+    for (lang, stat) in &result.language_stats {
         println!(
             "  {:<12} | Pure: {:>4} | Added: {:>4} | Removed: {:>4} | Noise: {:>4}",
             lang,
@@ -162,20 +212,234 @@ fn print_plain_report(
         );
     }
 
+    if !result.relocations.is_empty() {
+        println!("\nRenames and Copies:");
+        for relocation in &result.relocations {
+            println!("  {}", format_relocation(relocation));
+        }
+    }
+
     if per_file {
         println!("\nFile Details:");
         for file in files {
             println!(
-                "  {:<30} [{}] | Pure: {:>3}",
+                "  {:<30} [{}] | Pure: {:>3} | {}",
                 file.path,
                 file.language,
-                file.lang_stats.net_pure()
+                file.lang_stats.net_pure(),
+                file.status
+            );
+        }
+        if !generated.is_empty() {
+            println!("\nGenerated Files:");
+            for file in generated {
+                println!(
+                    "  {:<30} [{}] | Pure: {:>3} | {}",
+                    file.path,
+                    file.language,
+                    file.lang_stats.net_pure(),
+                    file.status
+                );
+            }
+        }
+        if !skipped.is_empty() {
+            println!("\nSkipped Files:");
+            for file in skipped {
+                println!("  {:<30} | {}", file.path, file.reason);
+            }
+        }
+    }
+    println!();
+}
+
+/// Counts skipped files per reason, e.g. `3 (binary: 2, too large: 1)`.
+fn summarize_skipped(skipped: &[SkippedFile]) -> String {
+    let mut by_reason: BTreeMap<SkipReason, usize> = BTreeMap::new();
+    for file in skipped {
+        *by_reason.entry(file.reason).or_default() += 1;
+    }
+    let counts: Vec<String> = by_reason
+        .iter()
+        .map(|(reason, count)| format!("{reason}: {count}"))
+        .collect();
+    format!("{} ({})", skipped.len(), counts.join(", "))
+}
+
+/// Prints one row per commit, oldest first, or a JSON array of [`CommitStats`].
+pub fn print_log(commits: &[CommitStats], format: OutputFormat, ci: bool) {
+    if format == OutputFormat::Json {
+        let json =
+            serde_json::to_string_pretty(commits).expect("Failed to serialize commit log to JSON");
+        println!("{json}");
+        return;
+    }
+
+    let use_color = !ci && format == OutputFormat::Human;
+    if use_color {
+        println!("{}", "PureCode Commit Log".bold().underline());
+    } else {
+        println!("PureCode Commit Log");
+    }
+    println!("Commits: {}", commits.len());
+    println!();
+
+    for commit in commits {
+        let stat = &commit.lang_stats;
+        let sha = &commit.sha[..commit.sha.len().min(7)];
+        if use_color {
+            println!(
+                "  {} | Pure: {:>5} | Added: {:>5} | Removed: {:>5} | Noise: {:>4} | Complexity: {:>6.1} | {:<16} | {}",
+                sha.yellow(),
+                stat.net_pure(),
+                stat.pure_added.to_string().green(),
+                stat.pure_removed.to_string().red(),
+                stat.noise_added() + stat.noise_removed(),
+                commit.complexity_score,
+                commit.author,
+                commit.subject
+            );
+        } else {
+            println!(
+                "  {} | Pure: {:>5} | Added: {:>5} | Removed: {:>5} | Noise: {:>4} | Complexity: {:>6.1} | {:<16} | {}",
+                sha,
+                stat.net_pure(),
+                stat.pure_added,
+                stat.pure_removed,
+                stat.noise_added() + stat.noise_removed(),
+                commit.complexity_score,
+                commit.author,
+                commit.subject
             );
         }
     }
+
+    // The commit carrying most of the review load stands out in a long series.
+    let total: f64 = commits.iter().map(|c| c.complexity_score).sum();
+    let largest = commits
+        .iter()
+        .max_by(|a, b| a.complexity_score.total_cmp(&b.complexity_score));
+    if let Some(largest) = largest.filter(|_| total > 0.0) {
+        println!();
+        println!(
+            "Largest Commit: {} ({:.0}% of review complexity)",
+            &largest.sha[..largest.sha.len().min(7)],
+            largest.complexity_score / total * 100.0
+        );
+    }
     println!();
 }
 
+/// Prints per-author statistics, the authors with the most net pure lines first.
+pub fn print_authors(authors: &[AuthorStats], format: OutputFormat, ci: bool) {
+    if format == OutputFormat::Json {
+        let json =
+            serde_json::to_string_pretty(authors).expect("Failed to serialize authors to JSON");
+        println!("{json}");
+        return;
+    }
+
+    let use_color = !ci && format == OutputFormat::Human;
+    if use_color {
+        println!("{}", "PureCode Authors Report".bold().underline());
+    } else {
+        println!("PureCode Authors Report");
+    }
+    println!("Authors: {}", authors.len());
+    println!();
+
+    for author in authors {
+        let stat = &author.lang_stats;
+        let commits = author
+            .commits
+            .map(|c| format!(" | Commits: {c:>4}"))
+            .unwrap_or_default();
+        if use_color {
+            println!(
+                "  {:<24} | Pure: {:>6} | Added: {:>6} | Removed: {:>6} | Comments: {:>5} | Docstrings: {:>5} | Files: {:>4}{}",
+                author.author.blue(),
+                stat.net_pure(),
+                stat.pure_added.to_string().green(),
+                stat.pure_removed.to_string().red(),
+                stat.comment_lines_added,
+                stat.docstring_lines_added,
+                author.files,
+                commits
+            );
+        } else {
+            println!(
+                "  {:<24} | Pure: {:>6} | Added: {:>6} | Removed: {:>6} | Comments: {:>5} | Docstrings: {:>5} | Files: {:>4}{}",
+                author.author,
+                stat.net_pure(),
+                stat.pure_added,
+                stat.pure_removed,
+                stat.comment_lines_added,
+                stat.docstring_lines_added,
+                author.files,
+                commits
+            );
+        }
+    }
+    println!();
+}
+
+pub fn print_history_json(rows: &[HistoryRow]) {
+    let json = serde_json::to_string_pretty(rows).expect("Failed to serialize history to JSON");
+    println!("{json}");
+}
+
+pub fn print_history_csv(rows: &[HistoryRow]) {
+    println!("date,sha,language,files,pure_lines,comment_lines,docstring_lines,blank_lines,comment_density,docstring_density");
+    for row in rows {
+        println!(
+            "{},{},{},{},{},{},{},{},{:.4},{:.4}",
+            row.date,
+            row.sha,
+            csv_field(&row.language),
+            row.files,
+            row.pure_lines,
+            row.comment_lines,
+            row.docstring_lines,
+            row.blank_lines,
+            row.comment_density,
+            row.docstring_density
+        );
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// e.g. "renamed  src/old.rs -> src/new.rs (95% similar)"
+fn format_relocation(relocation: &Relocation) -> String {
+    let similarity = relocation
+        .similarity
+        .map(|s| format!(" ({s}% similar)"))
+        .unwrap_or_default();
+    format!(
+        "{:<8} {} -> {}{}",
+        relocation.status, relocation.from, relocation.to, similarity
+    )
+}
+
+fn collect_relocations(stats: &[FileStats]) -> Vec<Relocation> {
+    stats
+        .iter()
+        .filter(|file| file.is_relocated())
+        .map(|file| Relocation {
+            from: file.old_path.clone().unwrap_or_default(),
+            to: file.path.clone(),
+            status: file.status,
+            similarity: file.similarity,
+        })
+        .collect()
+}
+
 fn complexity_bucket(score: f64) -> &'static str {
     if score < 50.0 {
         "light"
@@ -186,14 +450,19 @@ fn complexity_bucket(score: f64) -> &'static str {
     }
 }
 
-fn aggregate_by_language(stats: &[FileStats]) -> HashMap<String, LangStats> {
-    let mut lang_map: HashMap<String, LangStats> = HashMap::new();
+fn aggregate_by_language(stats: &[FileStats]) -> BTreeMap<String, LangStats> {
+    let mut lang_map: BTreeMap<String, LangStats> = BTreeMap::new();
     for file in stats {
         let entry = lang_map.entry(file.language.clone()).or_default();
         entry.total_added += file.lang_stats.total_added;
         entry.total_removed += file.lang_stats.total_removed;
         entry.pure_added += file.lang_stats.pure_added;
         entry.pure_removed += file.lang_stats.pure_removed;
+        entry.pure_moved += file.lang_stats.pure_moved;
+        entry.reformatted_added += file.lang_stats.reformatted_added;
+        entry.reformatted_removed += file.lang_stats.reformatted_removed;
+        entry.reformatted_words_added += file.lang_stats.reformatted_words_added;
+        entry.reformatted_words_removed += file.lang_stats.reformatted_words_removed;
         entry.comment_lines_added += file.lang_stats.comment_lines_added;
         entry.comment_lines_removed += file.lang_stats.comment_lines_removed;
         entry.docstring_lines_added += file.lang_stats.docstring_lines_added;
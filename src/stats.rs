@@ -1,12 +1,18 @@
-use serde::Serialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct AnalysisResult {
     pub summary: LangStats,
-    pub language_stats: HashMap<String, LangStats>, // Keyed by Language::to_string()
+    pub language_stats: BTreeMap<String, LangStats>, // Keyed by Language::to_string()
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file_stats: Option<Vec<FileStats>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub relocations: Vec<Relocation>,
+    pub skipped: Vec<SkippedFile>,
+    /// Generated and vendored files, kept out of `summary` and `language_stats`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generated: Option<GeneratedStats>,
     pub complexity_score: f64,
     pub token_estimate: u64,
     pub mode: String, // "diff" or "snapshot"
@@ -15,16 +21,212 @@ pub struct AnalysisResult {
 #[derive(Debug, Clone, Serialize)]
 pub struct FileStats {
     pub path: String,
+    /// Source path of a rename or copy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    pub status: FileStatus,
+    /// Similarity index git reported for a rename or copy, in percent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<u8>,
     pub language: String, // String for serialization, but internal logic uses Language
+    /// Encoding the file was decoded from, when it was not plain UTF-8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// Whether the file is generated or vendored rather than written by hand.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub generated: bool,
+    pub lang_stats: LangStats,
+}
+
+impl FileStats {
+    pub fn new(path: String, language: String, status: FileStatus) -> Self {
+        Self {
+            path,
+            old_path: None,
+            status,
+            similarity: None,
+            language,
+            encoding: None,
+            generated: false,
+            lang_stats: LangStats::default(),
+        }
+    }
+
+    /// Whether the file was renamed or copied rather than written from scratch.
+    #[must_use]
+    pub fn is_relocated(&self) -> bool {
+        matches!(self.status, FileStatus::Renamed | FileStatus::Copied)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Added,
+    Deleted,
+    #[default]
+    Modified,
+    Renamed,
+    Copied,
+}
+
+impl std::fmt::Display for FileStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            FileStatus::Added => "added",
+            FileStatus::Deleted => "deleted",
+            FileStatus::Modified => "modified",
+            FileStatus::Renamed => "renamed",
+            FileStatus::Copied => "copied",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Totals of the generated and vendored files of an analysis.
+#[derive(Debug, Clone, Serialize)]
+pub struct GeneratedStats {
+    pub files: usize,
     pub lang_stats: LangStats,
 }
 
-#[derive(Debug, Default, Clone, Copy, Serialize)]
+/// Why a file was left out of the statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    Binary,
+    TooLarge,
+    Unreadable,
+    /// Left out by the `include` or `exclude` globs.
+    Excluded,
+    Generated,
+    /// Marked `linguist-documentation` in `.gitattributes`.
+    Documentation,
+    /// Marked `-linguist-detectable` in `.gitattributes`.
+    Undetectable,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SkipReason::Binary => "binary",
+            SkipReason::TooLarge => "too large",
+            SkipReason::Unreadable => "unreadable",
+            SkipReason::Excluded => "excluded",
+            SkipReason::Generated => "generated",
+            SkipReason::Documentation => "documentation",
+            SkipReason::Undetectable => "undetectable",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A file that was seen but not counted, so a low file count can be explained.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: SkipReason,
+}
+
+impl SkippedFile {
+    pub fn new(path: impl Into<String>, reason: SkipReason) -> Self {
+        Self {
+            path: path.into(),
+            reason,
+        }
+    }
+}
+
+/// Changes introduced by a single commit of a range.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitStats {
+    pub sha: String,
+    pub author: String,
+    pub subject: String,
+    pub files_changed: usize,
+    pub complexity_score: f64,
+    pub lang_stats: LangStats,
+}
+
+impl CommitStats {
+    pub fn new(commit: CommitInfo, files: &[FileStats]) -> Self {
+        let lang_stats = aggregate_stats(files);
+        Self {
+            sha: commit.sha,
+            author: commit.author,
+            subject: commit.subject,
+            files_changed: files.len(),
+            complexity_score: calculate_complexity(&lang_stats),
+            lang_stats,
+        }
+    }
+}
+
+/// Lines attributed to one author, keyed by the name after mailmap.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AuthorStats {
+    pub author: String,
+    /// Commits counted for the author; only set when attributing commits rather than blame.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commits: Option<usize>,
+    pub files: usize,
+    pub lang_stats: LangStats,
+}
+
+/// Identifies a commit without its changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitInfo {
+    pub sha: String,
+    pub author: String,
+    pub subject: String,
+    /// Committer time in seconds since the epoch.
+    pub time: i64,
+}
+
+/// Snapshot totals of one language at one sampled commit.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryRow {
+    pub date: String,
+    pub sha: String,
+    pub language: String,
+    pub files: usize,
+    pub pure_lines: i64,
+    pub comment_lines: i64,
+    pub docstring_lines: i64,
+    pub blank_lines: i64,
+    /// Share of non-blank lines that are comments.
+    pub comment_density: f64,
+    /// Share of non-blank lines that are docstrings.
+    pub docstring_density: f64,
+}
+
+/// A renamed or copied file, listed on its own so moved code is not mistaken for new code.
+#[derive(Debug, Clone, Serialize)]
+pub struct Relocation {
+    pub from: String,
+    pub to: String,
+    pub status: FileStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<u8>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 pub struct LangStats {
     pub total_added: i64,
     pub total_removed: i64,
     pub pure_added: i64,
     pub pure_removed: i64,
+    /// Pure lines removed in one place and added back in another. They are
+    /// included in `pure_added` and `pure_removed` but not in the complexity score.
+    pub pure_moved: i64,
+    /// Pure lines that only changed formatting (whitespace, line wrapping, quote
+    /// style). They are included in `pure_added` and `pure_removed`.
+    pub reformatted_added: i64,
+    pub reformatted_removed: i64,
+    /// Words on the reformatted lines, included in `code_words_added` and
+    /// `code_words_removed`.
+    pub reformatted_words_added: i64,
+    pub reformatted_words_removed: i64,
     pub comment_lines_added: i64,
     pub comment_lines_removed: i64,
     pub docstring_lines_added: i64,
@@ -59,6 +261,11 @@ pub fn aggregate_stats(stats: &[FileStats]) -> LangStats {
         acc.total_removed += file.lang_stats.total_removed;
         acc.pure_added += file.lang_stats.pure_added;
         acc.pure_removed += file.lang_stats.pure_removed;
+        acc.pure_moved += file.lang_stats.pure_moved;
+        acc.reformatted_added += file.lang_stats.reformatted_added;
+        acc.reformatted_removed += file.lang_stats.reformatted_removed;
+        acc.reformatted_words_added += file.lang_stats.reformatted_words_added;
+        acc.reformatted_words_removed += file.lang_stats.reformatted_words_removed;
         acc.comment_lines_added += file.lang_stats.comment_lines_added;
         acc.comment_lines_removed += file.lang_stats.comment_lines_removed;
         acc.docstring_lines_added += file.lang_stats.docstring_lines_added;
@@ -71,11 +278,29 @@ pub fn aggregate_stats(stats: &[FileStats]) -> LangStats {
     })
 }
 
+/// Drops reformatted lines from the pure and total counts, and their words
+/// from the word counts, so they no longer contribute to net pure lines, noise
+/// ratio, complexity or the token estimate.
+pub fn exclude_reformatted(stats: &mut [FileStats]) {
+    for file in stats {
+        let s = &mut file.lang_stats;
+        s.pure_added -= s.reformatted_added;
+        s.pure_removed -= s.reformatted_removed;
+        s.total_added -= s.reformatted_added;
+        s.total_removed -= s.reformatted_removed;
+        s.code_words_added -= s.reformatted_words_added;
+        s.code_words_removed -= s.reformatted_words_removed;
+    }
+}
+
 #[must_use]
 pub fn calculate_complexity(stats: &LangStats) -> f64 {
     // complexity = pure_added * 1.0 + pure_removed * 0.5 + noise_added * 0.1 + noise_removed * 0.05
-    (stats.pure_added as f64 * 1.0)
-        + (stats.pure_removed as f64 * 0.5)
+    // Moved lines are left out of both pure terms.
+    let pure_added = (stats.pure_added - stats.pure_moved).max(0);
+    let pure_removed = (stats.pure_removed - stats.pure_moved).max(0);
+    (pure_added as f64 * 1.0)
+        + (pure_removed as f64 * 0.5)
         + (stats.noise_added() as f64 * 0.1)
         + (stats.noise_removed() as f64 * 0.05)
 }
@@ -1,30 +1,48 @@
 #[cfg(test)]
 mod tests {
     use crate::classifier::{get_classifier, LineType};
-    use crate::language::Language;
+    use crate::config::Config;
+    use crate::files::{analyze_content, analyze_files, WalkOptions};
+    use crate::generated::GeneratedDetector;
+    use crate::language::{Language, Languages};
+    use crate::parser::{parse_diff_into, DiffBuilder};
+    use crate::stats::{SkipReason, SkippedFile};
     use std::fs;
+    use std::io::Cursor;
 
     #[test]
     fn test_python_fixture() {
         let content = fs::read_to_string("src/tests/fixtures/python.py").unwrap();
-        let mut classifier = get_classifier(Language::Python);
+        let mut classifier = get_classifier(&Language::Python);
         let lines: Vec<&str> = content.lines().collect();
 
         assert_eq!(classifier.classify(lines[0]), LineType::Pure); // def hello():
         assert_eq!(classifier.classify(lines[1]), LineType::Pure); // print
         assert_eq!(classifier.classify(lines[2]), LineType::Blank); // empty
         assert_eq!(classifier.classify(lines[3]), LineType::Comment); // # comment
-        assert_eq!(classifier.classify(lines[4]), LineType::Docstring); // """
-        assert_eq!(classifier.classify(lines[5]), LineType::Docstring); // This is a
-        assert_eq!(classifier.classify(lines[6]), LineType::Docstring); // multiline docstring
-        assert_eq!(classifier.classify(lines[7]), LineType::Docstring); // """
+
+        // Not the first statement of the module, so a plain string expression.
+        assert_eq!(classifier.classify(lines[4]), LineType::Pure); // """
+        assert_eq!(classifier.classify(lines[5]), LineType::Pure); // This is a
+        assert_eq!(classifier.classify(lines[6]), LineType::Pure); // multiline docstring
+        assert_eq!(classifier.classify(lines[7]), LineType::Pure); // """
         assert_eq!(classifier.classify(lines[8]), LineType::Pure); // x = 1
+        assert_eq!(classifier.classify(lines[11]), LineType::Pure); // class Greeter:
+        assert_eq!(classifier.classify(lines[12]), LineType::Docstring); // r'''Greets people.'''
+        assert_eq!(classifier.classify(lines[14]), LineType::Pure); // def greet(self, name):
+        assert_eq!(classifier.classify(lines[15]), LineType::Docstring); // """
+        assert_eq!(classifier.classify(lines[16]), LineType::Docstring); // Return a greeting.
+        assert_eq!(classifier.classify(lines[17]), LineType::Docstring); // """
+        assert_eq!(classifier.classify(lines[18]), LineType::Pure); // query = f"""
+        assert_eq!(classifier.classify(lines[19]), LineType::Pure); // SELECT 'hi'
+        assert_eq!(classifier.classify(lines[20]), LineType::Pure); // """
+        assert_eq!(classifier.classify(lines[21]), LineType::Pure); // return query
     }
 
     #[test]
     fn test_html_fixture() {
         let content = fs::read_to_string("src/tests/fixtures/test.html").unwrap();
-        let mut classifier = get_classifier(Language::Html);
+        let mut classifier = get_classifier(&Language::Html);
         let lines: Vec<&str> = content.lines().collect();
 
         assert_eq!(classifier.classify(lines[0]), LineType::Comment); // <!-- start comment -->
@@ -37,4 +55,171 @@ mod tests {
         assert_eq!(classifier.classify(lines[7]), LineType::Comment); // comment
         assert_eq!(classifier.classify(lines[8]), LineType::Comment); // -->
     }
+
+    #[test]
+    fn test_ignore_files_are_honored() {
+        let paths = vec!["src/tests/fixtures/walk".to_string()];
+        let include = vec!["**/*.py".to_string()];
+        let analyzed = |no_ignore| {
+            let options = WalkOptions {
+                no_ignore,
+                jobs: 4,
+                ..WalkOptions::default()
+            };
+            // Parallel walks still report files sorted by path.
+            analyze_files(
+                &paths,
+                &include,
+                &[],
+                None,
+                options,
+                None,
+                &GeneratedDetector::default(),
+            )
+            .unwrap()
+            .files
+            .into_iter()
+            .map(|fs| fs.path)
+            .collect::<Vec<String>>()
+        };
+
+        assert_eq!(
+            analyzed(false),
+            [
+                "src/tests/fixtures/walk/keep.py",
+                "src/tests/fixtures/walk/src/model.py"
+            ]
+        );
+        assert_eq!(
+            analyzed(true),
+            [
+                "src/tests/fixtures/walk/build/out.py",
+                "src/tests/fixtures/walk/keep.py",
+                "src/tests/fixtures/walk/src/model.py",
+                "src/tests/fixtures/walk/src/model_pb2.py"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parallel_walk_matches_single_threaded_walk() {
+        let dir = std::env::temp_dir().join(format!("purecode-jobs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for i in 0..40 {
+            let sub = dir.join(format!("pkg{}", i % 4));
+            fs::create_dir_all(&sub).unwrap();
+            // Every third file is over the size limit below.
+            let body = if i % 3 == 0 {
+                "x = 1\n".repeat(50)
+            } else {
+                format!("# module {i}\nx = {i}\n")
+            };
+            fs::write(sub.join(format!("m{i:02}.py")), body).unwrap();
+        }
+
+        let paths = vec![dir.to_string_lossy().into_owned()];
+        let include = vec!["**/*.py".to_string()];
+        let analyzed = |jobs| {
+            let options = WalkOptions {
+                jobs,
+                max_file_size: 100,
+                ..WalkOptions::default()
+            };
+            let snapshot = analyze_files(
+                &paths,
+                &include,
+                &[],
+                None,
+                options,
+                None,
+                &GeneratedDetector::default(),
+            )
+            .unwrap();
+            let paths: Vec<String> = snapshot.files.iter().map(|fs| fs.path.clone()).collect();
+            (
+                paths,
+                serde_json::to_string(&snapshot.files).unwrap(),
+                snapshot.skipped,
+            )
+        };
+
+        let single = analyzed(1);
+        assert_eq!(analyzed(8), single);
+        assert_eq!(analyzed(0), single);
+
+        let (files, _, skipped) = single;
+        assert_eq!(files.len(), 26);
+        assert!(files.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(skipped.len(), 14);
+        assert!(skipped.iter().all(|s| s.reason == SkipReason::TooLarge));
+        assert!(skipped.windows(2).all(|w| w[0].path < w[1].path));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_excluded_files_are_reported_as_skipped() {
+        let paths = vec!["src/tests/fixtures/walk".to_string()];
+        let include = vec!["**/*.py".to_string()];
+        let snapshot = analyze_files(
+            &paths,
+            &include,
+            &[],
+            None,
+            WalkOptions::default(),
+            None,
+            &GeneratedDetector::default(),
+        )
+        .unwrap();
+
+        assert_eq!(snapshot.files.len(), 2);
+        assert_eq!(
+            snapshot.skipped,
+            [
+                SkippedFile::new("src/tests/fixtures/walk/.ignore", SkipReason::Excluded),
+                SkippedFile::new(
+                    "src/tests/fixtures/walk/src/.purecodeignore",
+                    SkipReason::Excluded
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_language_from_config() {
+        let config: Config = toml::from_str(
+            r##"
+            [[languages]]
+            name = "Elixir"
+            extensions = ["ex", "exs"]
+            line_comments = ["#"]
+            strings = ['"', '"""']
+            "##,
+        )
+        .unwrap();
+        let detector =
+            GeneratedDetector::default().with_languages(Languages::new(config.languages));
+
+        let content = "# Greets.\ndefmodule Greeter do\n  def hi, do: \"# not a comment\"\nend\n";
+        let language = detector.attributes().language("lib/greeter.ex", None);
+        let file_stats = analyze_content("lib/greeter.ex", &language, content);
+        assert_eq!(file_stats.language, "Elixir");
+        assert_eq!(file_stats.lang_stats.comment_lines_added, 1);
+        assert_eq!(file_stats.lang_stats.pure_added, 3);
+
+        let diff_input = "\
+--- a/test/greeter_test.exs
++++ b/test/greeter_test.exs
+@@ -1,0 +2,2 @@
++# Runs the greeter.
++Greeter.hi()
+";
+        let mut stats = Vec::new();
+        let mut builder = DiffBuilder::new(&mut stats, None).with_detector(detector);
+        parse_diff_into(Cursor::new(diff_input), &mut builder).unwrap();
+        builder.finish();
+        assert_eq!(stats[0].language, "Elixir");
+        assert_eq!(stats[0].lang_stats.comment_lines_added, 1);
+        assert_eq!(stats[0].lang_stats.pure_added, 1);
+    }
 }
@@ -7,3 +7,16 @@ This is a
 multiline docstring
 """
 x = 1
+
+
+class Greeter:
+    r'''Greets people.'''
+
+    def greet(self, name):
+        """
+        Return a greeting.
+        """
+        query = f"""
+        SELECT 'hi'
+        """
+        return query
@@ -0,0 +1 @@
+w = 4
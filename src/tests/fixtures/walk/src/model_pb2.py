@@ -0,0 +1 @@
+z = 3